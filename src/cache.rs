@@ -0,0 +1,220 @@
+use std::{collections::HashMap, fmt::Write as _, io::Result, path::PathBuf};
+
+use crate::{scanner::ScanOptions, tokens::{locate_bytes, TLit, TTy, Token, TokenList}};
+
+//Where cached token streams are stored, relative to the working directory.
+const CACHE_DIR: &str = ".rlox-cache";
+//Bump whenever the cache format or the scanner's output changes, so stale entries are never read.
+const CACHE_VERSION: u32 = 15;
+
+//Every token type, used to map names in the cache back to a TTy.
+//A type missing from this list just makes scripts using it miss the cache.
+const TYPES: &[TTy] = {
+    use TTy::*;
+    &[
+        LParen, RParen, LBrace, RBrace, LBracket, RBracket, Comma, Period, Minus, Plus, Semicolon, FSlash, Asterisk,
+        Bang, BangEq, Eq, EqEq, Gt, GtEq, Lt, LtEq, DotDot, DotDotEq, Ellipsis, QuestionDot, QuestionQuestion,
+        Pipe,
+        Ident, String, Number, Char,
+        And, Class, Else, False, Fn, For, If, Null, Or,
+        Print, Return, Super, This, True, Var, While,
+        Is, Const, Yield, Defer, Enum,
+        EOF,
+    ]
+};
+
+/// Looks up the tokens previously stored for `source` scanned with `options`, if any.
+/// A missing, stale or corrupt entry is a cache miss.
+pub(crate) fn load(source: &str, options: &ScanOptions) -> Option<TokenList> {
+    let text = std::fs::read_to_string(entry_path(source, options)).ok()?;
+    decode(&text, source, options)
+}
+
+/// Stores the tokens scanned from `source` with `options`.
+pub(crate) fn store(source: &str, options: &ScanOptions, tokens: &TokenList) -> Result<()> {
+    std::fs::create_dir_all(CACHE_DIR)?;
+    std::fs::write(entry_path(source, options), encode(options, tokens))
+}
+
+//The text of an entry: the header, then one line per token.
+fn encode(options: &ScanOptions, tokens: &TokenList) -> String {
+    let mut text = header(options);
+    for token in tokens {
+        text.push('\n');
+        encode_token(&mut text, token);
+    }
+    text
+}
+
+//The tokens in the text of an entry, unless it's stale, corrupt or cut short.
+fn decode(text: &str, source: &str, options: &ScanOptions) -> Option<TokenList> {
+    let mut lines = text.split('\n');
+    if lines.next()? != header(options) {
+        return None;
+    }
+
+    let chars: Vec<char> = source.chars().collect();
+    let types: HashMap<String, &TTy> = TYPES.iter()
+        .map(|ty| (format!("{ty:?}"), ty))
+        .collect();
+    let mut tokens: TokenList = lines.filter(|line| !line.is_empty())
+        .map(|line| decode_token(line, &chars, &types))
+        .collect::<Option<_>>()?;
+    //Spans out of order can only come from a corrupt entry
+    if !tokens.windows(2).all(|pair| pair[0].span().end <= pair[1].span().start) {
+        return None;
+    }
+    //A complete entry ends with EOF at the end of the source, so one cut short is never read
+    if tokens.last().is_none_or(|token| *token.ty() != TTy::EOF || token.span().end != chars.len()) {
+        return None;
+    }
+    locate_bytes(&mut tokens, &chars);
+    Some(tokens)
+}
+
+//Identifies the format of an entry and the scanner (and options) that produced it.
+fn header(options: &ScanOptions) -> String {
+    format!("rlox-tokens {CACHE_VERSION} {} {options:?}", env!("CARGO_PKG_VERSION"))
+}
+
+//Entries are named after a hash of the source and the header, so any change
+//to either lands in a different entry.
+fn entry_path(source: &str, options: &ScanOptions) -> PathBuf {
+    //64 bit FNV-1a: stable across runs and Rust versions, unlike DefaultHasher.
+    let hash = header(options).bytes().chain(source.bytes())
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    PathBuf::from(CACHE_DIR).join(format!("{hash:016x}.tokens"))
+}
+
+//One token per line: type, line, span and literal, separated by tabs.
+//The lexeme isn't stored: it's the span of the source the entry belongs to.
+fn encode_token(out: &mut String, token: &Token) {
+    let span = token.span();
+    let _ = write!(out, "{:?}\t{}\t{}\t{}\t", token.ty(), token.line(), span.start, span.end);
+    match token.literal() {
+        TLit::Null => out.push('N'),
+        TLit::Int(int) => { let _ = write!(out, "I{int}"); },
+        //Stored as raw bits so the value round-trips exactly
+        TLit::Number(num) => { let _ = write!(out, "F{:x}", num.to_bits()); },
+        TLit::Bool(b) => out.push_str(if *b { "B1" } else { "B0" }),
+        TLit::Char(ch) => { let _ = write!(out, "C{:x}", *ch as u32); },
+        TLit::String(s) => {
+            out.push('S');
+            for ch in s.chars() {
+                match ch {
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    _ => out.push(ch),
+                }
+            }
+        },
+    }
+}
+
+fn decode_token(line: &str, source: &[char], types: &HashMap<String, &TTy>) -> Option<Token> {
+    let mut fields = line.splitn(5, '\t');
+    let ty = (*types.get(fields.next()?)?).clone();
+    let line = fields.next()?.parse().ok()?;
+    let start: usize = fields.next()?.parse().ok()?;
+    let end: usize = fields.next()?.parse().ok()?;
+    let lexeme: String = source.get(start .. end)?.iter().collect();
+
+    let lit = fields.next()?;
+    let (tag, value) = (lit.get(.. 1)?, &lit[1 ..]);
+    let literal = match tag {
+        "N" => TLit::Null,
+        "I" => TLit::Int(value.parse().ok()?),
+        "F" => TLit::Number(f64::from_bits(u64::from_str_radix(value, 16).ok()?)),
+        "B" => TLit::Bool(value == "1"),
+        "C" => TLit::Char(char::from_u32(u32::from_str_radix(value, 16).ok()?)?),
+        "S" => {
+            let mut s = String::new();
+            let mut chars = value.chars();
+            while let Some(ch) = chars.next() {
+                s.push(match ch {
+                    '\\' => match chars.next()? {
+                        'n' => '\n',
+                        'r' => '\r',
+                        other => other,
+                    },
+                    _ => ch,
+                });
+            }
+            TLit::String(s)
+        },
+        _ => return None,
+    };
+
+    Some(Token::new(ty, lexeme, literal, line, start .. end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, TYPES};
+    use crate::{error_log::capture, scanner::{tests::{random, random_source}, ScanOptions, Scanner}, tokens::TTy};
+
+    //Every kind of literal, and strings with the chars the format escapes
+    const SOURCE: &str = "var s = \"a\\\\b\ttab\";\nvar t = r#\"x\\ny\"#;\nvar u = \"\"\"\n  line\r\n  \"\"\";\n\
+        var c = ['é', '\\n', '\\u{1F600}', '\\\\'];\nvar n = [0, 42, 9223372036854775807, 9223372036854775808, 0.1, 1e300, 2.5];\n\
+        var b = True and False or null;\nfn f(a, ...rest) { ret a?.b ?? 1..=2 |> g; }\n";
+
+    #[test]
+    fn entries_round_trip() {
+        let options = ScanOptions::default();
+        let tokens = Scanner::with_options(SOURCE.into(), options.clone()).scan_tokens();
+        assert_eq!(decode(&encode(&options, &tokens), SOURCE, &options), Some(tokens));
+
+        let mut rng = 0x0bad_5eed_1234_5678;
+        for _ in 0 .. 500 {
+            let source = random_source(&mut rng, 60);
+            let (tokens, _) = capture(|| Scanner::with_options(source.clone(), options.clone()).scan_tokens());
+            assert_eq!(decode(&encode(&options, &tokens), &source, &options), Some(tokens), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn stale_or_corrupt_entries_are_misses() {
+        let options = ScanOptions::default();
+        let tokens = Scanner::with_options(SOURCE.into(), options.clone()).scan_tokens();
+        let entry = encode(&options, &tokens);
+
+        //Another version or other options
+        assert_eq!(decode(&entry.replacen("rlox-tokens", "rlox-tokens 0", 1), SOURCE, &options), None);
+        assert_eq!(decode(&entry, SOURCE, &ScanOptions { print_fn: true, ..options.clone() }), None);
+        //Cut short anywhere
+        for (end, _) in entry.char_indices().skip(1) {
+            assert_eq!(decode(&entry[.. end], SOURCE, &options), None, "cut at {end}");
+        }
+        //Garbled anywhere: it may still happen to read as tokens, but mustn't panic
+        let chars: Vec<char> = entry.chars().collect();
+        let mut rng = 0x5eed;
+        for _ in 0 .. 2000 {
+            let mut garbled = chars.clone();
+            let i = random(&mut rng) % garbled.len();
+            garbled[i] = ['\t', '\n', '9', 'x', 'é', 'S', '\\', '-'][random(&mut rng) % 8];
+            decode(&garbled.iter().collect::<String>(), SOURCE, &options);
+        }
+        //An entry for a source that since changed length
+        assert_eq!(decode(&entry, &SOURCE[.. SOURCE.len() - 1], &options), None);
+    }
+
+    #[test]
+    fn types_lists_every_token_type() {
+        //Discriminants count up from 0, so the list has each one exactly once
+        let mut listed: Vec<usize> = TYPES.iter().map(|ty| ty.clone() as usize).collect();
+        listed.sort();
+        assert_eq!(listed, (0 .. TYPES.len()).collect::<Vec<_>>());
+        //...and EOF, the last variant, is in it. No wildcard: a new TTy won't compile until it's considered here.
+        use TTy::*;
+        let last = |ty: &TTy| match ty {
+            EOF => true,
+            LParen | RParen | LBrace | RBrace | LBracket | RBracket | Comma | Period | Minus | Plus | Semicolon | FSlash | Asterisk
+                | Bang | BangEq | Eq | EqEq | Gt | GtEq | Lt | LtEq | DotDot | DotDotEq | Ellipsis | QuestionDot | QuestionQuestion
+                | Pipe | Ident | String | Number | Char | And | Class | Else | False | Fn | For | If | Null | Or
+                | Print | Return | Super | This | True | Var | While | Is | Const | Yield | Defer | Enum => false,
+        };
+        assert!(last(&EOF));
+        assert_eq!(EOF as usize, TYPES.len() - 1);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{io::IsTerminal, path::Path};
+
+use crate::{config::Config, error_log::{Level, Lint}, explain::{self, explanation}, highlight::Format, scanner::ScanOptions, suggest::closest};
+
+pub(crate) const USAGE: &str = "\
+Usage: rlox [options] [script | -]
+       rlox [options] <command> <arg>
+
+Commands:
+  run <script | dir | ->   Run a script, a project directory (main.lox or lox.toml), or stdin
+  tokens <script>          Print the tokens of a script
+  lint <script>            Report errors and warnings in a script without running it
+  repl                     Start an interactive session (the default with no arguments)
+  watch <script>           Re-run a script whenever it changes
+  doc <script>             Print Markdown docs from /// comments
+  highlight <script>       Print a script with syntax highlighting
+  minify <script>          Print a script without comments or unneeded whitespace
+  tree <script>            Print the syntax tree of a script, comments and whitespace included
+  package <script>         Build a standalone executable that runs the script
+  explain <code | lint>    Describe an error code (E002) or a lint, with examples
+
+Options:
+  -e, --eval <code>        Run <code> instead of a script
+  -o, --output <path>      Where package writes the executable (default: the script's name)
+  --no-cache               Don't read or write the token cache (.rlox-cache/)
+  --max-string-len=N       Longest allowed string literal, in chars
+  --max-nesting=N          Deepest allowed nesting of brackets
+  --infer-semicolons       End statements at line breaks, so `;` can be left out
+  --check-round-trip       Report scanner bugs: places the tokens don't reproduce the source
+  --print-fn               Make `print` a function, `print(\"hi\", x)`, instead of a statement
+  --dialect=D              Language dialect: classic (the default), or print-fn, as with --print-fn
+  --tab-width=N            Columns between tab stops, for positions in diagnostics (default: 4)
+  --format=html|ansi       Output format for highlight (default: ansi)
+  --protocol=jsonl         Run the repl as a JSON lines protocol, for notebook frontends
+  --error-format=F         Print diagnostics as text (human, the default) or as JSON lines (json)
+  --no-color               Don't color diagnostics (also off when NO_COLOR is set, or stderr isn't a terminal)
+  -W, -A, -D <lint>        Warn about, allow or deny (report as an error) a kind of warning
+  --deny-warnings          Deny every lint that would otherwise warn
+  -h, --help               Print this message
+
+Lints: keyword-typo, mixed-indent (allowed by default), precision-loss,
+       chained-comparison, assign-in-condition";
+
+//Subcommands which take a single argument
+const COMMANDS: &[&str] = &["run", "tokens", "lint", "watch", "doc", "highlight", "minify", "tree", "package", "explain"];
+//Every long option, for typo suggestions
+const OPTIONS: &[&str] = &["--eval", "--output", "--no-cache", "--max-string-len", "--max-nesting", "--infer-semicolons", "--check-round-trip", "--print-fn", "--dialect", "--tab-width", "--format", "--protocol", "--error-format", "--no-color", "--deny-warnings", "--help"];
+
+/// What to do, according to the command line
+#[derive(Debug)]
+pub(crate) enum Command {
+    //Run a script, a project directory, or a program from stdin ("-")
+    Run(String),
+    //Run a snippet of code
+    Eval(String),
+    Tokens(String),
+    //Report the diagnostics of a script, and run nothing
+    Lint(String),
+    Repl,
+    //The REPL's machine protocol
+    Protocol,
+    Watch(String),
+    Doc(String),
+    Highlight(String, Format),
+    Minify(String),
+    Tree(String),
+    //The script, and where to write the executable
+    Package(String, String),
+    Explain(String),
+    Help,
+}
+
+/// How scripts are run
+#[derive(Debug, Clone)]
+pub(crate) struct RunOptions {
+    //Read tokens from (and save them to) the on-disk token cache.
+    //Only applies to script files; the REPL and stdin never use it.
+    pub use_cache: bool,
+    pub scan: ScanOptions,
+    //Color diagnostics, if stderr is a terminal
+    pub color: bool,
+    //Print diagnostics as JSON lines instead of text
+    pub json_errors: bool,
+    //Levels for lints from lox.toml, then from -W, -A and -D, in the order given
+    pub lints: Vec<(Lint, Level)>,
+    pub deny_warnings: bool,
+}
+
+/// Parses the command line arguments (without the program name), on top of the settings in `config`.
+/// Errors are messages meant for the user.
+pub(crate) fn parse(args: impl IntoIterator<Item = String>, config: &Config) -> Result<(Command, RunOptions), String> {
+    let mut options = RunOptions {
+        use_cache: config.use_cache,
+        scan: config.scan.clone(),
+        color: true,
+        json_errors: false,
+        lints: config.lints.clone(),
+        deny_warnings: false,
+    };
+    let mut format = Format::Ansi;
+    let mut eval = None;
+    let mut output = None;
+    let mut protocol = false;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        //Long options can be written `--name=value` or `--name value`
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if arg.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || inline_value.clone().or_else(|| args.next()).ok_or(format!("{name} expects a value"));
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            "-e" | "--eval" => eval = Some(value()?),
+            "-o" | "--output" => output = Some(value()?),
+            "--no-cache" => options.use_cache = false,
+            "--no-color" => options.color = false,
+            "--error-format" => options.json_errors = match value()?.as_str() {
+                "human" => false,
+                "json" => true,
+                other => return Err(format!("Unknown error format `{other}`. Expected human or json.")),
+            },
+            "-W" | "-A" | "-D" => {
+                let name = value()?;
+                let lint = Lint::from_name(&name).ok_or_else(|| unknown_lint(&name))?;
+                let level = match arg.as_str() {
+                    "-A" => Level::Allow,
+                    "-D" => Level::Deny,
+                    _ => Level::Warn,
+                };
+                options.lints.push((lint, level));
+            },
+            "--deny-warnings" => options.deny_warnings = true,
+            "--max-string-len" => options.scan.max_string_len = number(&name, value()?)?,
+            "--max-nesting" => options.scan.max_nesting = number(&name, value()?)?,
+            "--infer-semicolons" => options.scan.infer_semicolons = true,
+            "--check-round-trip" => options.scan.check_round_trip = true,
+            "--print-fn" => options.scan.print_fn = true,
+            "--dialect" => options.scan.print_fn = match value()?.as_str() {
+                "classic" => false,
+                "print-fn" => true,
+                other => return Err(format!("Unknown dialect `{other}`. Expected classic or print-fn.")),
+            },
+            "--tab-width" => options.scan.tab_width = number(&name, value()?)?,
+            "--format" => format = match value()?.as_str() {
+                "html" => Format::Html,
+                "ansi" => Format::Ansi,
+                other => return Err(format!("Unknown format `{other}`. Expected html or ansi.")),
+            },
+            "--protocol" => protocol = match value()?.as_str() {
+                "jsonl" => true,
+                other => return Err(format!("Unknown protocol `{other}`. Expected jsonl.")),
+            },
+            //A lone dash is stdin, not an option
+            "-" => positional.push(arg),
+            _ if name.starts_with('-') => return Err(unknown(&name, OPTIONS)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match (eval, positional.as_slice()) {
+        (Some(code), []) => Command::Eval(code),
+        (Some(_), _) => return Err("--eval can't be combined with a script or command".into()),
+        //A program piped in (`cat gen.lox | rlox`) is run whole, not fed to the REPL line by line
+        //Frontends speaking the protocol pipe stdin too
+        (None, []) if protocol => Command::Protocol,
+        (None, []) if !std::io::stdin().is_terminal() => Command::Run("-".into()),
+        (None, []) => Command::Repl,
+        (None, [cmd]) if cmd == "repl" => Command::Repl,
+        (None, [cmd]) if COMMANDS.contains(&cmd.as_str()) => return Err(format!("`{cmd}` expects an argument")),
+        (None, [script]) => {
+            //Most likely a mistyped command, unless there really is a file by that name
+            if !Path::new(script).exists() && closest(script, COMMANDS.iter().copied().chain(["repl"]), 2).is_some() {
+                return Err(unknown(script, &[COMMANDS, &["repl"]].concat()));
+            }
+            Command::Run(script.clone())
+        },
+        (None, [cmd, arg]) => match cmd.as_str() {
+            "run" => Command::Run(arg.clone()),
+            "tokens" => Command::Tokens(arg.clone()),
+            "lint" => Command::Lint(arg.clone()),
+            "watch" => Command::Watch(arg.clone()),
+            "doc" => Command::Doc(arg.clone()),
+            "minify" => Command::Minify(arg.clone()),
+            "tree" => Command::Tree(arg.clone()),
+            "explain" => match explanation(arg) {
+                Some(_) => Command::Explain(arg.clone()),
+                None => return Err(unknown_explanation(arg)),
+            },
+            "package" => Command::Package(arg.clone(), output.clone().unwrap_or_else(|| default_output(arg))),
+            "highlight" => Command::Highlight(arg.clone(), format),
+            //`rlox script.lox extra`
+            _ if Path::new(cmd).exists() => return Err("Too many arguments".into()),
+            _ => return Err(unknown(cmd, COMMANDS)),
+        },
+        (None, _) => return Err("Too many arguments".into()),
+    };
+    let command = match (protocol, command) {
+        (true, Command::Repl) => Command::Protocol,
+        (true, command @ Command::Protocol) | (false, command) => command,
+        (true, _) => return Err("--protocol only applies to the repl".into()),
+    };
+
+    Ok((command, options))
+}
+
+//`app.lox` is packaged as `app` (or `app.exe` on Windows), in the working directory.
+fn default_output(script: &str) -> String {
+    let stem = Path::new(script).file_stem().map_or("app".into(), |stem| stem.to_string_lossy());
+    format!("{stem}{}", std::env::consts::EXE_SUFFIX)
+}
+
+fn number(option: &str, value: String) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("{option} expects a number, got `{value}`"))
+}
+
+fn unknown_explanation(name: &str) -> String {
+    let names = explain::names();
+    match closest(name, names.iter().map(String::as_str), 2) {
+        Some(suggestion) => format!("No explanation for `{name}`. Did you mean `{suggestion}`?"),
+        None => format!("No explanation for `{name}`. Expected an error code, like E002, or a lint."),
+    }
+}
+
+pub(crate) fn unknown_lint(name: &str) -> String {
+    let names = Lint::ALL.iter().map(|lint| lint.name());
+    match closest(name, names.clone(), 2) {
+        Some(suggestion) => format!("Unknown lint `{name}`. Did you mean `{suggestion}`?"),
+        None => format!("Unknown lint `{name}`. Lints: {}", names.collect::<Vec<_>>().join(", ")),
+    }
+}
+
+//An error message for an unknown command or option, suggesting the closest known one.
+fn unknown(word: &str, known: &[&str]) -> String {
+    let kind = if word.starts_with('-') { "option" } else { "command" };
+    match closest(word, known.iter().copied(), 2) {
+        Some(suggestion) => format!("Unknown {kind} `{word}`. Did you mean `{suggestion}`?"),
+        None => format!("Unknown {kind} `{word}`"),
+    }
+}
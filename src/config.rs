@@ -0,0 +1,124 @@
+use std::{io::{Error, ErrorKind, Result}, path::Path};
+
+use crate::{cli::unknown_lint, error_log::{Level, Lint}, project::{manifest_value, MANIFEST}, scanner::ScanOptions};
+
+/// User settings from the nearest `lox.toml`, used as the defaults for command line flags.
+/// The keys are `prompt`, the booleans `cache`, `infer-semicolons`, `print-fn` and `deny-warnings`,
+/// the numbers `max-string-len`, `max-nesting` and `tab-width`, and the lists of lint names `allow`, `warn` and `deny`.
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    //Printed before each line read by the REPL
+    pub prompt: String,
+    //Whether scripts use the on-disk token cache (`--no-cache` turns it off)
+    pub use_cache: bool,
+    pub scan: ScanOptions,
+    //Levels for lints, in the order they apply. `-W`, `-A` and `-D` come after them.
+    pub lints: Vec<(Lint, Level)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prompt: "> ".into(),
+            use_cache: true,
+            scan: ScanOptions::default(),
+            lints: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the `lox.toml` in `dir` or the closest of its parents.
+    /// Without one, the defaults are used.
+    pub fn discover(dir: impl AsRef<Path>) -> Result<Self> {
+        let Some(path) = dir.as_ref().ancestors().map(|dir| dir.join(MANIFEST)).find(|path| path.is_file()) else {
+            return Ok(Self::default());
+        };
+
+        let text = std::fs::read_to_string(&path)?;
+        Self::parse(&text).map_err(|message| Error::new(ErrorKind::InvalidData, format!("{}: {message}", path.display())))
+    }
+
+    /// Reads the settings from the text of a manifest. Keys which aren't settings are ignored.
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Some(value) = manifest_value(text, "prompt") {
+            let (prompt, _) = value.strip_prefix('"').and_then(|v| v.split_once('"'))
+                .ok_or(format!("prompt expects a string, got `{value}`"))?;
+            config.prompt = prompt.to_owned();
+        }
+
+        for (key, setting) in [("cache", &mut config.use_cache), ("infer-semicolons", &mut config.scan.infer_semicolons), ("print-fn", &mut config.scan.print_fn)] {
+            if let Some(value) = manifest_value(text, key) {
+                *setting = value.parse().map_err(|_| format!("{key} expects true or false, got `{value}`"))?;
+            }
+        }
+
+        for (key, setting) in [("max-string-len", &mut config.scan.max_string_len), ("max-nesting", &mut config.scan.max_nesting), ("tab-width", &mut config.scan.tab_width)] {
+            if let Some(value) = manifest_value(text, key) {
+                *setting = value.parse().map_err(|_| format!("{key} expects a number, got `{value}`"))?;
+            }
+        }
+
+        for (key, level) in [("allow", Level::Allow), ("warn", Level::Warn), ("deny", Level::Deny)] {
+            if let Some(value) = manifest_value(text, key) {
+                for name in string_list(value).ok_or(format!("{key} expects a list of lint names, got `{value}`"))? {
+                    let lint = Lint::from_name(name).ok_or_else(|| format!("{key}: {}", unknown_lint(name)))?;
+                    config.lints.push((lint, level));
+                }
+            }
+        }
+
+        //Denies what warns after the lists, as --deny-warnings does after -W, -A and -D
+        if let Some(value) = manifest_value(text, "deny-warnings") {
+            let deny_warnings: bool = value.parse().map_err(|_| format!("deny-warnings expects true or false, got `{value}`"))?;
+            if deny_warnings {
+                for &lint in Lint::ALL {
+                    let level = config.lints.iter().rev().find(|(listed, _)| *listed == lint).map_or(lint.default_level(), |&(_, level)| level);
+                    if level == Level::Warn {
+                        config.lints.push((lint, Level::Deny));
+                    }
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+//The strings in a one line list, `["a", "b"]`
+fn string_list(value: &str) -> Option<Vec<&str>> {
+    let items = value.strip_prefix('[')?.strip_suffix(']')?.trim();
+    //A trailing comma is allowed
+    let items = items.strip_suffix(',').unwrap_or(items);
+    if items.is_empty() {
+        return Some(Vec::new());
+    }
+    items.split(',').map(|item| item.trim().strip_prefix('"')?.strip_suffix('"')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::error_log::{Level, Lint};
+
+    #[test]
+    fn lint_lists() {
+        let config = Config::parse("allow = [\"keyword-typo\"]\nwarn = [\"mixed-indent\", \"precision-loss\",]  # comment\ndeny = []\n").unwrap();
+        assert_eq!(config.lints, [(Lint::KeywordTypo, Level::Allow), (Lint::MixedIndent, Level::Warn), (Lint::PrecisionLoss, Level::Warn)]);
+
+        assert_eq!(Config::parse("deny = [\"keywrd-typo\"]").unwrap_err(), "deny: Unknown lint `keywrd-typo`. Did you mean `keyword-typo`?");
+        assert!(Config::parse("allow = \"keyword-typo\"").is_err());
+        assert!(Config::parse("allow = [keyword-typo]").is_err());
+    }
+
+    #[test]
+    fn deny_warnings() {
+        let config = Config::parse("deny-warnings = true\nallow = [\"keyword-typo\"]\nwarn = [\"mixed-indent\"]").unwrap();
+        let denied: Vec<Lint> = config.lints.iter().filter(|(_, level)| *level == Level::Deny).map(|&(lint, _)| lint).collect();
+        //Everything that warns, but not what's allowed, by default or in the list
+        assert_eq!(denied, [Lint::MixedIndent, Lint::PrecisionLoss, Lint::ChainedComparison, Lint::AssignInCondition]);
+        assert!(Config::parse("deny-warnings = false").unwrap().lints.is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{scanner::{ScanOptions, Scanner}, tokens::{TTy, Token, TriviaKind}};
+
+/// Kinds of node in the concrete syntax tree.
+/// Until there's a parser the only structure is bracket nesting; statements and expressions will get kinds of their own.
+/// Brackets nested deeper than options.max_nesting (already an error) don't get nodes, so walking the tree can't overflow the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeKind {
+    //The whole script
+    Script,
+    //From an opening bracket to its closing one, or to the end of the script if it's never closed
+    Parens,
+    Braces,
+    Brackets,
+}
+
+/// A child of a node
+#[derive(Debug, Clone)]
+pub(crate) enum Element {
+    Node(Node),
+    Token(Token),
+    //Trivia has no lexeme, so it carries its text
+    Trivia(TriviaKind, String),
+}
+
+/// A node of the concrete syntax tree: everything in its part of the source, tokens and trivia alike, in order.
+/// Nothing is dropped, so printing a tree (Display) gives back the source exactly, and tools like
+/// a formatter can rearrange code without losing comments or formatting.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub kind: NodeKind,
+    children: Vec<Element>,
+    //Length in chars, kept up to date as children are added
+    len: usize,
+}
+
+impl Element {
+    /// Length in chars
+    pub fn text_len(&self) -> usize {
+        match self {
+            Element::Node(node) => node.text_len(),
+            Element::Token(token) => token.span().len(),
+            Element::Trivia(_, text) => text.chars().count(),
+        }
+    }
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self { kind, children: Vec::new(), len: 0 }
+    }
+
+    //Until there's a formatter, only the tests look inside nodes.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn children(&self) -> &[Element] {
+        &self.children
+    }
+
+    /// Length in chars
+    pub fn text_len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, child: Element) {
+        self.len += child.text_len();
+        self.children.push(child);
+    }
+
+    /// An indented outline of the tree, one element per line with its char offsets:
+    /// `Var 0..3 "var"`. Nodes are positioned from their start at `offset`.
+    pub fn outline(&self, offset: usize) -> String {
+        let mut out = String::new();
+        self.push_outline(&mut out, offset, 0);
+        out
+    }
+
+    fn push_outline(&self, out: &mut String, mut offset: usize, depth: usize) {
+        let indent = "  ".repeat(depth);
+        *out += &format!("{indent}{:?} {offset}..{}\n", self.kind, offset + self.text_len());
+        for child in &self.children {
+            let end = offset + child.text_len();
+            match child {
+                Element::Node(node) => node.push_outline(out, offset, depth + 1),
+                Element::Token(token) => *out += &format!("{indent}  {:?} {offset}..{end} {:?}\n", token.ty(), token.lexeme()),
+                Element::Trivia(kind, text) => *out += &format!("{indent}  {kind:?} {offset}..{end} {text:?}\n"),
+            }
+            offset = end;
+        }
+    }
+}
+
+/// The source text the tree was built from.
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for child in &self.children {
+            match child {
+                Element::Node(node) => write!(f, "{node}")?,
+                Element::Token(token) => write!(f, "{}", token.lexeme())?,
+                Element::Trivia(_, text) => write!(f, "{text}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the concrete syntax tree of `source`. Errors are reported as the scanner finds them,
+/// but the tree is always built: unclosed brackets run to the end of the script,
+/// mismatched closing brackets are left where they are, and brackets nested too deeply are left as tokens.
+pub(crate) fn build(source: String, options: &ScanOptions) -> Node {
+    let chars: Vec<char> = source.chars().collect();
+    let (tokens, trivia) = Scanner::with_options(source, options.clone()).scan_with_trivia();
+    let mut trivia = trivia.into_iter().peekable();
+
+    //The script, then each bracket still open
+    let mut open = vec![Node::new(NodeKind::Script)];
+    //Brackets still open past max_nesting, which are only tokens
+    let mut too_deep: Vec<NodeKind> = Vec::new();
+    for token in tokens {
+        //How deep a bracket opened here would be
+        let depth = open.len();
+        let innermost = open.last_mut().expect("the script is never closed");
+        while let Some(trivia) = trivia.next_if(|trivia| trivia.span.start < token.span().start) {
+            innermost.push(Element::Trivia(trivia.kind, chars[trivia.span].iter().collect()));
+        }
+
+        match (opens(token.ty()), closes(token.ty())) {
+            (Some(kind), _) if depth > options.max_nesting => {
+                too_deep.push(kind);
+                innermost.push(Element::Token(token));
+            },
+            (Some(kind), _) => {
+                let mut node = Node::new(kind);
+                node.push(Element::Token(token));
+                open.push(node);
+            },
+            (_, Some(kind)) if !too_deep.is_empty() => {
+                if too_deep.last() == Some(&kind) {
+                    too_deep.pop();
+                }
+                innermost.push(Element::Token(token));
+            },
+            (_, Some(kind)) if kind == innermost.kind => {
+                innermost.push(Element::Token(token));
+                close(&mut open);
+            },
+            _ => {
+                if *token.ty() == TTy::EOF {
+                    while open.len() > 1 {
+                        close(&mut open);
+                    }
+                }
+                open.last_mut().expect("the script is never closed").push(Element::Token(token));
+            },
+        }
+    }
+
+    open.pop().expect("the script is never closed")
+}
+
+//Moves the innermost open node into its parent
+fn close(open: &mut Vec<Node>) {
+    let node = open.pop().expect("only brackets are closed");
+    open.last_mut().expect("the script is never closed").push(Element::Node(node));
+}
+
+fn opens(ty: &TTy) -> Option<NodeKind> {
+    match ty {
+        TTy::LParen => Some(NodeKind::Parens),
+        TTy::LBrace => Some(NodeKind::Braces),
+        TTy::LBracket => Some(NodeKind::Brackets),
+        _ => None,
+    }
+}
+
+fn closes(ty: &TTy) -> Option<NodeKind> {
+    match ty {
+        TTy::RParen => Some(NodeKind::Parens),
+        TTy::RBrace => Some(NodeKind::Braces),
+        TTy::RBracket => Some(NodeKind::Brackets),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build, Element, Node, NodeKind};
+    use crate::{error_log::capture, scanner::{tests::random_source, ScanOptions}};
+
+    //The tree of `source`, without printing any errors
+    fn tree_of(source: &str, options: &ScanOptions) -> Node {
+        capture(|| build(source.into(), options)).0
+    }
+
+    //The nodes directly inside `node`
+    fn child_nodes(node: &Node) -> Vec<&Node> {
+        node.children().iter().filter_map(|child| match child {
+            Element::Node(node) => Some(node),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn prints_back_the_source() {
+        let mut rng = 0x2545_f491_4f6c_dd1d;
+        for _ in 0 .. 3000 {
+            let source = random_source(&mut rng, 80) + "([{)]}";
+            let tree = tree_of(&source, &ScanOptions::default());
+            assert_eq!(tree.to_string(), source);
+            assert_eq!(tree.text_len(), source.chars().count());
+        }
+    }
+
+    #[test]
+    fn brackets_are_nodes() {
+        let tree = tree_of("fn f(a) { ret [a]; } (", &ScanOptions::default());
+        let kinds: Vec<NodeKind> = child_nodes(&tree).iter().map(|node| node.kind).collect();
+        assert_eq!(kinds, [NodeKind::Parens, NodeKind::Braces, NodeKind::Parens]);
+        let braces = child_nodes(&tree)[1];
+        assert_eq!(braces.to_string(), "{ ret [a]; }");
+        assert_eq!(child_nodes(braces)[0].kind, NodeKind::Brackets);
+        //The unclosed `(` runs to the end
+        assert_eq!(child_nodes(&tree)[2].to_string(), "(");
+    }
+
+    #[test]
+    fn brackets_past_max_nesting_are_tokens() {
+        let options = ScanOptions { max_nesting: 2, ..ScanOptions::default() };
+        let tree = tree_of("((((x))))()", &options);
+        let outer = child_nodes(&tree);
+        assert_eq!(outer.len(), 2);
+        let inner = child_nodes(outer[0]);
+        assert_eq!(inner.len(), 1);
+        assert!(child_nodes(inner[0]).is_empty());
+        assert_eq!(inner[0].to_string(), "(((x)))");
+        //The closers of the flattened brackets didn't close the nodes early
+        assert_eq!(outer[1].to_string(), "()");
+
+        //Without the cap this overflows the stack building, printing or dropping the tree
+        let source = "(".repeat(100_000);
+        let tree = tree_of(&source, &ScanOptions::default());
+        assert_eq!(tree.to_string(), source);
+        assert!(tree.outline(0).lines().count() > 100_000);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{scanner::{ScanOptions, Scanner}, tokens::{TTy, Token, Trivia, TriviaKind}};
+
+/// Generates Markdown API docs for the functions, classes and methods in `source`,
+/// using the `///` doc comments written directly above each declaration.
+pub(crate) fn generate(name: &str, source: String, options: &ScanOptions) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let (tokens, trivia) = Scanner::with_options(source, options.clone()).scan_with_trivia();
+
+    let mut out = format!("# {name}\n");
+    //Brace depth of the current token, and of the body of the class being documented (if any)
+    let mut depth: usize = 0;
+    let mut class: Option<(String, usize)> = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).filter(|next| *next.ty() == TTy::Ident);
+        match token.ty() {
+            TTy::LBrace => depth += 1,
+            TTy::RBrace => {
+                //A stray `}` doesn't take the depth below the top level
+                depth = depth.saturating_sub(1);
+                if class.as_ref().is_some_and(|(_, body)| depth < *body) {
+                    class = None;
+                }
+            },
+            //Only top level declarations are part of the API
+            TTy::Class if depth == 0 => if let Some(name) = next {
+                out += &format!("\n## class {}\n", name.lexeme());
+                push_doc(&mut out, token, &tokens[.. i], &trivia, &chars);
+                class = Some((name.lexeme().to_owned(), depth + 1));
+            },
+            TTy::Fn if depth == 0 => if let Some(name) = next {
+                out += &format!("\n## fn {}({})\n", name.lexeme(), params(&tokens[i + 2 ..]));
+                push_doc(&mut out, token, &tokens[.. i], &trivia, &chars);
+            },
+            //Methods are declared without `fn`: `name(params) { ... }`, directly in a class body
+            TTy::Ident => if let Some((class, _)) = class.as_ref().filter(|(_, body)| *body == depth) {
+                //A method starts right after the class' `{`, or the `}` of the previous method
+                let prev = i.checked_sub(1).map(|prev| tokens[prev].ty());
+                let at_decl = matches!(prev, Some(TTy::LBrace | TTy::RBrace));
+                if at_decl && tokens.get(i + 1).is_some_and(|next| *next.ty() == TTy::LParen) {
+                    out += &format!("\n### {class}.{}({})\n", token.lexeme(), params(&tokens[i + 1 ..]));
+                    push_doc(&mut out, token, &tokens[.. i], &trivia, &chars);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    out
+}
+
+//Renders the parameter list starting at the `(` in tokens, e.g. "a, b = 2, ...rest".
+fn params(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens.iter().skip(1).take_while(|token| *token.ty() != TTy::RParen) {
+        match token.ty() {
+            TTy::Comma => out += ", ",
+            TTy::Eq => out += " = ",
+            _ => out += token.lexeme(),
+        }
+    }
+    out
+}
+
+//Appends the doc comments attached to `decl`: those between it and the token before it.
+fn push_doc(out: &mut String, decl: &Token, before: &[Token], trivia: &[Trivia], chars: &[char]) {
+    let from = before.last().map_or(0, |prev| prev.span().end);
+    let to = decl.span().start;
+
+    let docs = trivia.iter()
+        .filter(|trivia| trivia.kind == TriviaKind::DocComment)
+        .filter(|trivia| trivia.span.start >= from && trivia.span.end <= to);
+    for doc in docs {
+        let text: String = chars[doc.span.clone()].iter().collect();
+        //Strip the `///` and the conventional space after it
+        let text = text[3 ..].strip_prefix(' ').unwrap_or(&text[3 ..]);
+        out.push('\n');
+        out.push_str(text.trim_end());
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+    use crate::scanner::ScanOptions;
+
+    #[test]
+    fn stray_closing_brace() {
+        let docs = generate("t", "}\n/// d\nfn f(a) {}".into(), &ScanOptions::default());
+        assert_eq!(docs, "# t\n\n## fn f(a)\n\nd\n");
+    }
+}
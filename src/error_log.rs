@@ -1,7 +1,326 @@
-pub fn error(line: usize, message: impl ToString) {
-    report(line, "".into(), message.to_string());
+use std::{fmt::{Display, Formatter}, sync::{atomic::{AtomicBool, AtomicU8, Ordering}, Mutex, PoisonError}};
+
+use crate::{json::Json, line_index::LineIndex};
+
+/// Why running a script failed.
+/// Parsing, resolving and running will each get a variant once those phases exist.
+#[derive(Debug)]
+pub(crate) enum LoxError {
+    //The scanner reported errors. They've already been printed, as they were found.
+    Lex,
+    //The script (or its project) couldn't be read
+    Io(std::io::Error),
+}
+
+impl LoxError {
+    /// The exit code for the process, following the book (and sysexits.h).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            //EX_DATAERR
+            LoxError::Lex => 65,
+            //EX_IOERR
+            LoxError::Io(_) => 74,
+        }
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Lex => write!(f, "The script has syntax errors."),
+            LoxError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for LoxError {
+    fn from(e: std::io::Error) -> Self {
+        LoxError::Io(e)
+    }
+}
+
+/// Stable codes for each kind of error, shown with the error and explained by `rlox explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorCode {
+    //New codes go at the end, so existing ones never change.
+    UnexpectedChar,
+    UnterminatedString,
+    StringTooLong,
+    UnterminatedChar,
+    InvalidChar,
+    InvalidNumber,
+    NestingTooDeep,
+    ScannerBug,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = {
+        use ErrorCode::*;
+        &[UnexpectedChar, UnterminatedString, StringTooLong, UnterminatedChar, InvalidChar, InvalidNumber, NestingTooDeep, ScannerBug]
+    };
+
+    /// The code as it's shown: `E002`
+    pub fn code(self) -> String {
+        format!("E{:03}", self as usize + 1)
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|error| error.code().eq_ignore_ascii_case(code))
+    }
+}
+
+/// Categories of warnings, which can each be allowed, warned about or denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lint {
+    //An identifier that looks like a misspelled keyword: `whille (x) {`
+    KeywordTypo,
+    //Indentation using both tabs and spaces. Allowed unless turned on with `-W mixed-indent`.
+    MixedIndent,
+    //A number literal that an f64 can't hold exactly: `12345678901234567890`
+    PrecisionLoss,
+    //Comparisons written as a chain: `a < b < c`
+    ChainedComparison,
+    //An assignment as the condition of an if or while: `if (x = 5)`
+    AssignInCondition,
+}
+
+impl Lint {
+    pub const ALL: &'static [Lint] = &[Lint::KeywordTypo, Lint::MixedIndent, Lint::PrecisionLoss, Lint::ChainedComparison, Lint::AssignInCondition];
+
+    /// The name used on the command line: `-A keyword-typo`
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::KeywordTypo => "keyword-typo",
+            Lint::MixedIndent => "mixed-indent",
+            Lint::PrecisionLoss => "precision-loss",
+            Lint::ChainedComparison => "chained-comparison",
+            Lint::AssignInCondition => "assign-in-condition",
+        }
+    }
+
+    //The level of the lint when none is given on the command line
+    pub fn default_level(self) -> Level {
+        match self {
+            Lint::KeywordTypo | Lint::PrecisionLoss | Lint::ChainedComparison | Lint::AssignInCondition => Level::Warn,
+            Lint::MixedIndent => Level::Allow,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// What to do when a lint fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level {
+    //Starts at 1, so 0 can mean "not set" in LEVELS
+    Allow = 1,
+    Warn,
+    //Report it as an error
+    Deny,
+}
+
+//How serious a diagnostic is
+#[derive(Clone, Copy)]
+enum Severity {
+    Error,
+    Warning,
+    //Extra information attached to an error or warning, like where something began
+    Note,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+            Severity::Note => "Note",
+        }
+    }
+
+    //The name in machine-readable output
+    fn id(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    //SGR escape code for the severity's name
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;34m",
+        }
+    }
+}
+
+//What a diagnostic was reported for
+#[derive(Clone, Copy)]
+enum Origin {
+    Error(ErrorCode),
+    Lint(Lint),
 }
 
-fn report(line: usize, context: String, message: String) {
-    eprintln!("[Line {line}] Error ({context}): {message}");
-}
\ No newline at end of file
+/// A diagnostic as data, for machine-readable output. See `capture`.
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostic {
+    //"error", "warning" or "note"
+    pub severity: &'static str,
+    //The error code (`E002`) or lint name, if it has one
+    pub code: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub notes: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    pub fn to_json(&self) -> Json {
+        Json::Object(vec![
+            ("severity".into(), Json::String(self.severity.into())),
+            ("code".into(), self.code.clone().map_or(Json::Null, Json::String)),
+            ("line".into(), Json::Number(self.line as f64)),
+            ("column".into(), Json::Number(self.column as f64)),
+            ("message".into(), Json::String(self.message.clone())),
+            ("notes".into(), Json::Array(self.notes.iter().map(Diagnostic::to_json).collect())),
+        ])
+    }
+}
+
+//Set whenever an error is reported. Atomic since scan_parallel reports from many threads.
+static HAD_ERROR: AtomicBool = AtomicBool::new(false);
+//Set whenever a lint fires, even an allowed one.
+static HAD_LINT: AtomicBool = AtomicBool::new(false);
+//Whether diagnostics are colored. Off until main finds stderr is a terminal.
+static COLOR: AtomicBool = AtomicBool::new(false);
+//Whether diagnostics are printed as JSON lines (--error-format=json) rather than text.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+//The Level of each lint, indexed by `Lint as usize`, or 0 for the lint's default level.
+static LEVELS: [AtomicU8; Lint::ALL.len()] = [const { AtomicU8::new(0) }; Lint::ALL.len()];
+//While capturing, diagnostics are collected here instead of printed.
+static CAPTURED: Mutex<Option<Vec<Diagnostic>>> = Mutex::new(None);
+//Held for the whole of a capture, so concurrent captures (tests, say) don't collect each other's diagnostics.
+static CAPTURING: Mutex<()> = Mutex::new(());
+
+//Reports an error at the char `offset` in the source described by `index`.
+pub fn error(index: &LineIndex, offset: usize, code: ErrorCode, message: impl ToString) {
+    error_with_notes(index, offset, code, message, &[]);
+}
+
+//Reports an error, with notes pointing at related places: `(offset, message)`.
+pub fn error_with_notes(index: &LineIndex, offset: usize, code: ErrorCode, message: impl ToString, notes: &[(usize, &str)]) {
+    report(index, Severity::Error, Some(Origin::Error(code)), offset, &message.to_string(), notes);
+    HAD_ERROR.store(true, Ordering::Relaxed);
+}
+
+//Reports a warning from `lint` at the char `offset`, at the lint's level.
+//Warnings don't count as errors, unless the lint is denied.
+pub fn warning(index: &LineIndex, offset: usize, lint: Lint, message: impl ToString) {
+    HAD_LINT.store(true, Ordering::Relaxed);
+    let message = message.to_string();
+    match level(lint) {
+        Level::Allow => {},
+        Level::Warn => report(index, Severity::Warning, Some(Origin::Lint(lint)), offset, &message, &[]),
+        //Named by the lint, rather than an error code
+        Level::Deny => {
+            report(index, Severity::Error, Some(Origin::Lint(lint)), offset, &message, &[]);
+            HAD_ERROR.store(true, Ordering::Relaxed);
+        },
+    }
+}
+
+//Has any error been reported since the last reset?
+pub fn had_error() -> bool {
+    HAD_ERROR.load(Ordering::Relaxed)
+}
+
+//Has any lint fired since the last reset, whether or not it was reported?
+pub fn had_lint() -> bool {
+    HAD_LINT.load(Ordering::Relaxed)
+}
+
+pub fn reset() {
+    HAD_ERROR.store(false, Ordering::Relaxed);
+    HAD_LINT.store(false, Ordering::Relaxed);
+}
+
+pub fn level(lint: Lint) -> Level {
+    match LEVELS[lint as usize].load(Ordering::Relaxed) {
+        level if level == Level::Allow as u8 => Level::Allow,
+        level if level == Level::Warn as u8 => Level::Warn,
+        level if level == Level::Deny as u8 => Level::Deny,
+        _ => lint.default_level(),
+    }
+}
+
+pub fn set_level(lint: Lint, level: Level) {
+    LEVELS[lint as usize].store(level as u8, Ordering::Relaxed);
+}
+
+//Collects diagnostics reported while `f` runs, instead of printing them.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let _capturing = CAPTURING.lock().unwrap_or_else(PoisonError::into_inner);
+    *CAPTURED.lock().unwrap_or_else(PoisonError::into_inner) = Some(Vec::new());
+    let result = f();
+    let captured = CAPTURED.lock().unwrap_or_else(PoisonError::into_inner).take();
+    (result, captured.unwrap_or_default())
+}
+
+//Turns colored diagnostics on or off.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+//Turns printing diagnostics as JSON lines on or off.
+pub fn set_json(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+//Prints a diagnostic and its notes, indented under it, or as one JSON line.
+//Written in one go, so diagnostics from parallel scanner threads can't interleave.
+fn report(index: &LineIndex, severity: Severity, origin: Option<Origin>, offset: usize, message: &str, notes: &[(usize, &str)]) {
+    let mut captured = CAPTURED.lock().unwrap_or_else(PoisonError::into_inner);
+    if captured.is_some() || JSON_OUTPUT.load(Ordering::Relaxed) {
+        let diagnostic = |severity: Severity, origin: Option<Origin>, offset: usize, message: &str| {
+            let (line, column) = index.line_col(offset);
+            let code = origin.map(|origin| match origin {
+                Origin::Error(code) => code.code(),
+                Origin::Lint(lint) => lint.name().to_owned(),
+            });
+            Diagnostic { severity: severity.id(), code, line, column, message: message.to_owned(), notes: Vec::new() }
+        };
+        let mut out = diagnostic(severity, origin, offset, message);
+        out.notes = notes.iter().map(|&(offset, note)| diagnostic(Severity::Note, None, offset, note)).collect();
+        match captured.as_mut() {
+            Some(captured) => captured.push(out),
+            None => eprintln!("{}", out.to_json()),
+        }
+        return;
+    }
+    drop(captured);
+
+    let mut out = label(index, severity, origin, offset, message);
+    for &(offset, note) in notes {
+        out += "\n  ";
+        out += &label(index, Severity::Note, None, offset, note);
+    }
+    eprintln!("{out}");
+}
+
+//`[Line 1:5] Error[E002]: message`, or `[Line 1:5] Warning: message [lint-name]`
+fn label(index: &LineIndex, severity: Severity, origin: Option<Origin>, offset: usize, message: &str) -> String {
+    let (line, col) = index.line_col(offset);
+    let (name, message) = match origin {
+        Some(Origin::Error(code)) => (format!("{}[{}]", severity.name(), code.code()), message.to_owned()),
+        Some(Origin::Lint(lint)) => (severity.name().to_owned(), format!("{message} [{}]", lint.name())),
+        None => (severity.name().to_owned(), message.to_owned()),
+    };
+    match COLOR.load(Ordering::Relaxed) {
+        true => format!("\x1b[1m[Line {line}:{col}]\x1b[0m {}{name}\x1b[0m: {message}", severity.color()),
+        false => format!("[Line {line}:{col}] {name}: {message}"),
+    }
+}
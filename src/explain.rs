@@ -0,0 +1,179 @@
+use crate::error_log::{ErrorCode, Lint};
+
+/// The extended description of an error code (`E002`) or a lint (`keyword-typo`), for `rlox explain`.
+pub(crate) fn explanation(name: &str) -> Option<String> {
+    if let Some(code) = ErrorCode::from_code(name) {
+        return Some(format!("{}\n\n{}", code.code(), error_text(code)));
+    }
+    Lint::from_name(name).map(|lint| format!("{}\n\n{}", lint.name(), lint_text(lint)))
+}
+
+/// Every name `explanation` knows, for suggestions.
+pub(crate) fn names() -> Vec<String> {
+    ErrorCode::ALL.iter().map(|code| code.code())
+        .chain(Lint::ALL.iter().map(|lint| lint.name().to_owned()))
+        .collect()
+}
+
+fn error_text(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::UnexpectedChar => "\
+A char that can't start any token.
+
+Lox has no `%`, `&`, `^` or `~` operators, and a lone `|` or `?` isn't an
+operator either: only `|>`, `?.` and `??` are.
+
+    var x = a % b;
+    if (a & b) print x;
+
+Use a function for the missing operator, or the keyword form:
+
+    var x = mod(a, b);
+    if (a and b) print x;",
+
+        ErrorCode::UnterminatedString => "\
+A string literal with no closing quote.
+
+A string in double quotes ends with its line, so the quote is missing from
+the line the note points at.
+
+    print \"hello;
+    print \"world\";
+
+Close the string:
+
+    print \"hello\";
+    print \"world\";
+
+Strings that span lines are written in triple quotes, which drop the
+indentation of the closing quotes from every line:
+
+    print \"\"\"
+        Hello,
+        world
+        \"\"\";
+
+An unterminated triple-quoted string is reported where it starts. A raw
+string, r#\"...\"#, can also span lines, and is only closed by a quote
+followed by as many #s as opened it.",
+
+        ErrorCode::StringTooLong => "\
+A string literal longer than the limit, which is 1048576 chars by default.
+
+The limit guards against runaway strings, such as a missing closing quote
+swallowing the rest of a generated script. Raise it with
+--max-string-len=N, or `max-string-len = N` in lox.toml, if the script
+really needs a string that long.",
+
+        ErrorCode::UnterminatedChar => "\
+A char literal with no closing quote before the end of the line.
+
+Char literals can't span lines.
+
+    var quote = ';
+
+Close it on the same line, escaping a quote inside it:
+
+    var quote = '\\'';",
+
+        ErrorCode::InvalidChar => "\
+A char literal that doesn't hold exactly one char.
+
+Char literals hold a single char or escape sequence. Escapes are \\n, \\r, \\t,
+\\0, \\\\, \\', \\\" and \\u{...} with a hex code point.
+
+    var empty = '';
+    var word = 'ab';
+    var bell = '\\a';
+
+Use a string for anything else:
+
+    var empty = \"\";
+    var word = \"ab\";
+    var bell = '\\u{7}';",
+
+        ErrorCode::InvalidNumber => "\
+A number literal that can't be read as a number.
+
+A number has digits, and at most one decimal point followed by more digits.
+
+    var version = 1.2.3;
+
+Use a string, or separate numbers:
+
+    var version = \"1.2.3\";",
+
+        ErrorCode::NestingTooDeep => "\
+Brackets, (), [] and {}, nested deeper than the limit, which is 256 by default.
+
+Deep nesting is almost always generated code or a missing closing bracket.
+The limit keeps later phases from running out of stack. Split the
+expression into variables or functions, or raise the limit with
+--max-nesting=N, or `max-nesting = N` in lox.toml.",
+
+        ErrorCode::ScannerBug => "\
+The scanner's tokens and trivia don't reproduce the source exactly.
+
+This is only checked with --check-round-trip. It's a bug in rlox, not in
+the script: please report it, along with the script.",
+    }
+}
+
+fn lint_text(lint: Lint) -> &'static str {
+    match lint {
+        Lint::KeywordTypo => "\
+An identifier that looks like a misspelled keyword, where only the keyword
+would make sense.
+
+    whille (x) { prnt x; }
+    var done = true;
+
+Keywords are case sensitive, and the booleans are True and False:
+
+    while (x) { print x; }
+    var done = True;",
+
+        Lint::MixedIndent => "\
+A line indented with both tabs and spaces, or with the other one from the
+first indented line of the script. Allowed by default: turn it on with
+-W mixed-indent.
+
+Columns in diagnostics count a tab as reaching the next tab stop, which is
+set with --tab-width=N (4 by default).",
+
+        Lint::PrecisionLoss => "\
+A number literal that a 64 bit float can't hold exactly. The warning shows
+the value it becomes.
+
+    var big = 12345678901234567890;
+    var pi = 3.14159265358979323846;
+
+Integers up to 9223372036854775807 are exact. Beyond that, and for
+fractions, only about 17 significant digits are kept. Write the value it
+really has, or keep it as a string.",
+
+        Lint::ChainedComparison => "\
+Comparisons written as a chain. They don't chain: `a < b < c` compares the
+bool from `a < b` with c.
+
+    if (0 < x < 10) print x;
+
+Compare each pair, joined with `and`:
+
+    if (0 < x and x < 10) print x;",
+
+        Lint::AssignInCondition => "\
+An assignment as the condition of an if or while, which is usually a
+mistyped `==`.
+
+    if (x = 5) print x;
+
+Compare instead:
+
+    if (x == 5) print x;
+
+If the assignment is intended, wrap it in another pair of parentheses:
+
+    while ((line = next())) print line;",
+    }
+}
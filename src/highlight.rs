@@ -0,0 +1,137 @@
+use std::ops::Range;
+
+use crate::{scanner::{ScanOptions, Scanner}, tokens::{TTy, TriviaKind}};
+
+/// Semantic classes of source text, for syntax highlighters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HighlightKind {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Operator,
+    //Brackets, commas and semicolons
+    Punctuation,
+    Identifier,
+}
+
+/// Output formats for render
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    //A <pre> block with a <span class="lox-..."> around each classified span
+    Html,
+    //Terminal escape codes
+    Ansi,
+}
+
+impl HighlightKind {
+    //CSS class suffix used in HTML output
+    fn name(self) -> &'static str {
+        match self {
+            HighlightKind::Keyword => "keyword",
+            HighlightKind::String => "string",
+            HighlightKind::Number => "number",
+            HighlightKind::Comment => "comment",
+            HighlightKind::Operator => "operator",
+            HighlightKind::Punctuation => "punctuation",
+            HighlightKind::Identifier => "identifier",
+        }
+    }
+
+    //SGR escape code used in ANSI output, if the kind is colored at all
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            HighlightKind::Keyword => Some("\x1b[1;35m"),
+            HighlightKind::String => Some("\x1b[32m"),
+            HighlightKind::Number => Some("\x1b[36m"),
+            HighlightKind::Comment => Some("\x1b[90m"),
+            HighlightKind::Operator => Some("\x1b[33m"),
+            HighlightKind::Punctuation | HighlightKind::Identifier => None,
+        }
+    }
+}
+
+/// Renders `source` with syntax highlighting. Every char of the source is kept.
+pub(crate) fn render(source: String, format: Format, options: &ScanOptions) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let spans = highlight(source, options);
+
+    let mut out = String::new();
+    if format == Format::Html {
+        out += "<pre class=\"lox\"><code>";
+    }
+
+    let mut pos = 0;
+    for (span, kind) in spans {
+        push_text(&mut out, &chars[pos .. span.start], format);
+        let text = &chars[span.clone()];
+        match format {
+            Format::Html => {
+                out += &format!("<span class=\"lox-{}\">", kind.name());
+                push_text(&mut out, text, format);
+                out += "</span>";
+            },
+            Format::Ansi => match kind.ansi() {
+                Some(color) => {
+                    out += color;
+                    push_text(&mut out, text, format);
+                    out += "\x1b[0m";
+                },
+                None => push_text(&mut out, text, format),
+            },
+        }
+        pos = span.end;
+    }
+    push_text(&mut out, &chars[pos ..], format);
+
+    if format == Format::Html {
+        out += "</code></pre>\n";
+    }
+    out
+}
+
+//Appends source text, escaping it for the output format.
+fn push_text(out: &mut String, text: &[char], format: Format) {
+    for &ch in text {
+        match (format, ch) {
+            (Format::Html, '<') => out.push_str("&lt;"),
+            (Format::Html, '>') => out.push_str("&gt;"),
+            (Format::Html, '&') => out.push_str("&amp;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Classifies every token and comment in `source`, in source order.
+/// Spans are char offsets. Whitespace (and anything the scanner rejected) has no class.
+pub(crate) fn highlight(source: String, options: &ScanOptions) -> Vec<(Range<usize>, HighlightKind)> {
+    let (tokens, trivia) = Scanner::with_options(source, options.clone()).scan_with_trivia();
+
+    let mut spans: Vec<(Range<usize>, HighlightKind)> = tokens.iter()
+        .filter_map(|token| Some((token.span(), classify(token.ty())?)))
+        .chain(trivia.into_iter().filter_map(|trivia| match trivia.kind {
+            TriviaKind::Comment | TriviaKind::DocComment => Some((trivia.span, HighlightKind::Comment)),
+            TriviaKind::Whitespace | TriviaKind::Skipped => None,
+        }))
+        .collect();
+    spans.sort_by_key(|(span, _)| span.start);
+    spans
+}
+
+//No wildcard: every new token type has to be given a class here.
+fn classify(ty: &TTy) -> Option<HighlightKind> {
+    use TTy::*;
+
+    let kind = match ty {
+        LParen | RParen | LBrace | RBrace | LBracket | RBracket | Comma | Semicolon => HighlightKind::Punctuation,
+        Period | Minus | Plus | FSlash | Asterisk | Bang | BangEq | Eq | EqEq | Gt | GtEq | Lt | LtEq
+            | DotDot | DotDotEq | Ellipsis | QuestionDot | QuestionQuestion | Pipe => HighlightKind::Operator,
+        Ident => HighlightKind::Identifier,
+        String | Char => HighlightKind::String,
+        Number => HighlightKind::Number,
+        And | Class | Else | False | Fn | For | If | Null | Or | Print | Return | Super | This | True
+            | Var | While | Is | Const | Yield | Defer | Enum => HighlightKind::Keyword,
+        EOF => return None,
+    };
+    Some(kind)
+}
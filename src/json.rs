@@ -0,0 +1,323 @@
+use std::fmt::{Display, Formatter};
+
+//Deepest nesting of arrays and objects accepted, so a hostile message can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// A JSON value, for the REPL's machine protocol. Objects keep their keys in order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        match parser.pos < parser.chars.len() {
+            true => Err(parser.error("Unexpected text after the value")),
+            false => Ok(value),
+        }
+    }
+
+    /// The value of `key`, if this is an object with that key.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Compact JSON, on one line.
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            //JSON has no infinity or NaN
+            Json::Number(num) if !num.is_finite() => write!(f, "null"),
+            Json::Number(num) => write!(f, "{num}"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if ch < ' ' => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{ch}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+//Is `text` a number as JSON writes them? Rust also parses `01`, `1.` and `.5`, which JSON doesn't allow.
+fn is_number(text: &str) -> bool {
+    let digits = |text: &str| text.len() - text.trim_start_matches(|ch: char| ch.is_ascii_digit()).len();
+
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let int = digits(text);
+    if int == 0 || int > 1 && text.starts_with('0') {
+        return false;
+    }
+    let mut rest = &text[int ..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = digits(fraction);
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len ..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
+        }
+        rest = &exponent[len ..];
+    }
+    rest.is_empty()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(']') {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.eat(']') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                Ok(Json::Array(items))
+            },
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat('}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        fields.push((key, self.value(depth + 1)?));
+                        if self.eat('}') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                Ok(Json::Object(fields))
+            },
+            Some(ch) if *ch == '-' || ch.is_ascii_digit() => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|ch| matches!(ch, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start .. self.pos].iter().collect();
+                match is_number(&text) {
+                    true => text.parse().map(Json::Number).map_err(|_| self.error("Invalid number")),
+                    false => Err(self.error("Invalid number")),
+                }
+            },
+            Some(_) => Err(self.error("Expected a value")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    //A string, starting at its opening quote
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let ch = *self.chars.get(self.pos).ok_or_else(|| self.error("Unterminated string"))?;
+            self.pos += 1;
+            match ch {
+                '"' => return Ok(s),
+                ch if ch < ' ' => return Err(self.error("Unescaped control char in string")),
+                '\\' => {
+                    let escape = *self.chars.get(self.pos).ok_or_else(|| self.error("Unterminated string"))?;
+                    self.pos += 1;
+                    s.push(match escape {
+                        '"' | '\\' | '/' => escape,
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("Unknown escape")),
+                    });
+                },
+                _ => s.push(ch),
+            }
+        }
+    }
+
+    //The char of a \u escape, after the `u`. Chars outside the BMP are written as two escapes (a surrogate pair).
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xd800 .. 0xdc00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("Invalid \\u escape"));
+        }
+        //Straight after, inside the string: not eat, which would skip whitespace
+        if self.chars.get(self.pos .. self.pos + 2) != Some(&['\\', 'u']) {
+            return Err(self.error("Unpaired surrogate in \\u escape"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xdc00 .. 0xe000).contains(&low) {
+            return Err(self.error("Invalid surrogate pair in \\u escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+            .ok_or_else(|| self.error("Invalid surrogate pair in \\u escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.get(self.pos .. self.pos + 4)
+            //from_str_radix alone would also take a sign: \u+041
+            .filter(|digits| digits.iter().all(char::is_ascii_hexdigit))
+            .ok_or_else(|| self.error("Invalid \\u escape"))?
+            .iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("Invalid \\u escape"))
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let len = word.chars().count();
+        if self.chars.get(self.pos .. self.pos + len).is_some_and(|chars| chars.iter().copied().eq(word.chars())) {
+            self.pos += len;
+            Ok(value)
+        } else {
+            Err(self.error("Expected a value"))
+        }
+    }
+
+    //Consumes `ch` (after any whitespace) if it's next
+    fn eat(&mut self, ch: char) -> bool {
+        self.skip_whitespace();
+        let found = self.chars.get(self.pos) == Some(&ch);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        match self.eat(ch) {
+            true => Ok(()),
+            false => Err(self.error(&format!("Expected `{ch}`"))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|ch| matches!(ch, ' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{message} at char {}", self.pos + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, MAX_DEPTH};
+
+    #[test]
+    fn values() {
+        let json = Json::parse(r#" {"a": [1, -2.5e3, true, null], "b": "x\"\\\/\n\u00e9"} "#).unwrap();
+        assert_eq!(json, Json::Object(vec![
+            ("a".into(), Json::Array(vec![Json::Number(1.0), Json::Number(-2500.0), Json::Bool(true), Json::Null])),
+            ("b".into(), Json::String("x\"\\/\né".into())),
+        ]));
+        assert_eq!(json.to_string(), r#"{"a":[1,-2500,true,null],"b":"x\"\\/\né"}"#);
+    }
+
+    #[test]
+    fn malformed() {
+        for text in ["", "{", "[1,]", "[1 2]", "{\"a\" 1}", "{a: 1}", "\"abc", "\"a\\", "tru", "nul", "1 2", "'a'",
+            "01", "1.", ".5", "-", "1e", "1e+", "+1", "\"\\x\"", "\"a\nb\"", "\"\\u12\"", "\"\\u+041\"", "\"\\u-041\"", "\"\\u 041\""] {
+            assert!(Json::parse(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn surrogates() {
+        assert_eq!(Json::parse(r#""\ud83d\ude00""#), Ok(Json::String("😀".into())));
+        assert_eq!(Json::parse(r#""\u0041""#), Ok(Json::String("A".into())));
+        //A high surrogate needs a low one after it, and a low one can't stand alone
+        for text in [r#""\ud83d""#, r#""\ud83dx""#, r#""\ud83d\u0041""#, r#""\ude00""#, r#""\ude00\ud83d""#, r#""\ud83d \ude00""#] {
+            assert!(Json::parse(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 2)).is_err());
+        //Deep enough to overflow the stack without the limit
+        assert!(Json::parse(&nested(100_000)).is_err());
+    }
+}
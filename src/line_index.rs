@@ -0,0 +1,137 @@
+//Maps offsets into a source buffer to line/column positions.
+//Built once per script and shared by the scanner (for token lines)
+//and the error log (for rendering positions).
+pub(crate) struct LineIndex {
+    //The offset of the first char of every line, in order.
+    //Always contains at least 0, for the first line.
+    line_starts: Vec<usize>,
+    //The line number of offset 0. Usually 1, but a chunk of a larger
+    //script begins partway through it.
+    first_line: usize,
+    //The offset of every tab, in order, for working out columns.
+    tabs: Vec<usize>,
+    //How many columns apart tab stops are.
+    tab_width: usize,
+}
+
+impl LineIndex {
+    /// Indexes a buffer whose first char is on line `first_line` of the script.
+    /// Columns count a tab as reaching the next multiple of `tab_width`, as editors show it.
+    pub fn starting_at(buf: &[char], first_line: usize, tab_width: usize) -> Self {
+        let mut line_starts = vec![0];
+        let tabs = (0 .. buf.len()).filter(|&i| buf[i] == '\t').collect();
+        let mut i = 0;
+        while i < buf.len() {
+            match line_break_len(buf, i) {
+                Some(len) => {
+                    i += len;
+                    line_starts.push(i);
+                }
+                None => i += 1,
+            }
+        }
+
+        Self { line_starts, first_line, tabs, tab_width: tab_width.max(1) }
+    }
+
+    //The position in line_starts of the line containing `offset`.
+    fn line_idx(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        }
+    }
+
+    /// The 1-based line containing the char at `offset`.
+    /// Line breaks belong to the line they terminate.
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.line_idx(offset) + self.first_line
+    }
+
+    /// The 1-based (line, column) of the char at `offset`.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let idx = self.line_idx(offset);
+        let line_start = self.line_starts[idx];
+
+        //The column reached so far (0-based), and the offset it was reached at
+        let (mut col, mut pos) = (0, line_start);
+        let first_tab = self.tabs.partition_point(|&tab| tab < line_start);
+        for &tab in self.tabs[first_tab ..].iter().take_while(|&&tab| tab < offset) {
+            col = ((col + tab - pos) / self.tab_width + 1) * self.tab_width;
+            pos = tab + 1;
+        }
+        (idx + self.first_line, col + offset - pos + 1)
+    }
+}
+
+//If a line break starts at buf[i], returns how many chars it spans.
+//CRLF counts as a single break, so Windows files don't double count lines.
+//A lone CR and the Unicode line/paragraph separators are breaks as well.
+pub(crate) fn line_break_len(buf: &[char], i: usize) -> Option<usize> {
+    match buf.get(i)? {
+        '\r' if buf.get(i + 1) == Some(&'\n') => Some(2),
+        '\n' | '\r' | '\u{85}' | '\u{2028}' | '\u{2029}' => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineIndex;
+    use crate::{error_log::capture, scanner::{ScanOptions, Scanner}};
+
+    fn index(source: &str, tab_width: usize) -> LineIndex {
+        let chars: Vec<char> = source.chars().collect();
+        LineIndex::starting_at(&chars, 1, tab_width)
+    }
+
+    #[test]
+    fn tabs_reach_the_next_tab_stop() {
+        let index = index("\tx\n  \ty\nab\t\tz\n\t \tw", 4);
+        assert_eq!(index.line_col(1), (1, 5));
+        //A tab part way to a stop only reaches it
+        assert_eq!(index.line_col(6), (2, 5));
+        assert_eq!(index.line_col(12), (3, 9));
+        assert_eq!(index.line_col(17), (4, 9));
+        //Tabs on earlier lines don't count
+        assert_eq!(index.line_col(8), (3, 1));
+    }
+
+    #[test]
+    fn columns_count_chars() {
+        //é and 😀 are several bytes, but one column each
+        let index = index("é\tx\n😀😀\ty", 4);
+        assert_eq!(index.line_col(2), (1, 5));
+        assert_eq!(index.line_col(7), (2, 5));
+    }
+
+    #[test]
+    fn tab_width_zero_is_one() {
+        let index = index("\t\tx", 0);
+        assert_eq!(index.line_col(2), (1, 3));
+
+        //Nor does it divide by zero reporting an error after a tab
+        let options = ScanOptions { tab_width: 0, ..ScanOptions::default() };
+        let (_, diagnostics) = capture(|| Scanner::with_options("\t\t%".into(), options).scan_tokens());
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 3));
+    }
+
+    #[test]
+    fn line_breaks() {
+        //CRLF is one break, and a lone CR, NEL, LS and PS are breaks too
+        let index = index("a\r\nb\rc\u{85}d\u{2028}e\u{2029}f\ng", 4);
+        let lines: Vec<usize> = [0, 1, 2, 3, 5, 7, 9, 11, 13].iter().map(|&offset| index.line_of(offset)).collect();
+        assert_eq!(lines, [1, 1, 1, 2, 3, 4, 5, 6, 7]);
+        //After a CRLF, the next line starts at column 1
+        assert_eq!(index.line_col(3), (2, 1));
+        assert_eq!(index.line_col(9), (5, 1));
+    }
+
+    #[test]
+    fn chunks_start_part_way_through() {
+        let chars: Vec<char> = "x\n\ty".chars().collect();
+        let index = LineIndex::starting_at(&chars, 10, 8);
+        assert_eq!(index.line_col(0), (10, 1));
+        assert_eq!(index.line_col(3), (11, 9));
+    }
+}
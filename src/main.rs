@@ -1,59 +1,300 @@
-use std::io::{Result, BufRead, Write};
+use std::{io::{BufRead, IsTerminal, Read, Write}, time::Duration};
 
+use cli::{Command, RunOptions};
+use config::Config;
+use error_log::{Level, Lint, LoxError};
+use highlight::Format;
+use project::Project;
 use scanner::Scanner;
 
+mod cache;
+mod cli;
+mod config;
+mod cst;
+mod doc;
 mod error_log;
+mod explain;
+mod highlight;
+mod json;
+mod line_index;
+mod minify;
+mod package;
+mod protocol;
+mod project;
 mod scanner;
+mod suggest;
 mod tokens;
 
-fn main() -> Result<()> {
-    let mut args = std::env::args().skip(1);
+type Result<T> = std::result::Result<T, LoxError>;
 
-    if args.len() > 1 {
-        eprintln!("Usage: rlox [script]");
-    } else if args.len() == 1 {
-        run_script(args.next().expect("a script to run"))?;
-    } else {
-        start_repl()?;
+fn main() {
+    //A packaged app runs its own script, rather than taking rlox's arguments
+    if let Some((config, script)) = package::embedded() {
+        let options = RunOptions { use_cache: false, scan: config.scan, color: true, json_errors: false, lints: config.lints, deny_warnings: false };
+        error_log::set_color(std::io::stderr().is_terminal());
+        set_lint_levels(&options);
+        exit_on_error(run(script, &options));
+        return;
     }
 
+    let config = discover_config(std::env::current_dir());
+    let (command, options) = parse_args(&config);
+    //A project is scanned with the settings in its own lox.toml, wherever it's run from. Flags still win.
+    let (config, command, options) = match &command {
+        Command::Run(target) if target != "-" && std::path::Path::new(target).is_dir() => {
+            let config = discover_config(Ok(target.into()));
+            let (command, options) = parse_args(&config);
+            (config, command, options)
+        },
+        _ => (config, command, options),
+    };
+
+    //https://no-color.org
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    error_log::set_color(options.color && !no_color_env && std::io::stderr().is_terminal());
+    error_log::set_json(options.json_errors);
+    set_lint_levels(&options);
+
+    let result = match command {
+        Command::Run(target) if target == "-" => run_stdin(&options),
+        Command::Run(target) if std::path::Path::new(&target).is_dir() => run_project(&target, &options),
+        Command::Run(script) => run_script(script, &options),
+        Command::Eval(code) => run(code, &RunOptions { use_cache: false, ..options }),
+        Command::Tokens(script) => print_tokens(&script, &options),
+        Command::Lint(script) => lint_script(&script, &options),
+        Command::Repl => start_repl(&options, &config.prompt),
+        Command::Protocol => protocol::serve(&options),
+        Command::Watch(script) => watch_script(&script, &options),
+        Command::Doc(script) => read_source(&script).map(|source| print!("{}", doc::generate(&script, source, &options.scan))),
+        Command::Highlight(script, format) => highlight_script(&script, format, &options),
+        Command::Minify(script) => minify_script(&script, &options),
+        Command::Tree(script) => read_source(&script).map(|source| print!("{}", cst::build(source, &options.scan).outline(0))),
+        Command::Package(script, out) => package_script(&script, &out, &options),
+        Command::Explain(name) => {
+            println!("{}", explain::explanation(&name).unwrap_or_default());
+            Ok(())
+        },
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        },
+    };
+    exit_on_error(result);
+}
+
+//Sets the level of each lint in options.lints, in order, then denies what still warns with options.deny_warnings
+fn set_lint_levels(options: &RunOptions) {
+    for &(lint, level) in &options.lints {
+        error_log::set_level(lint, level);
+    }
+    if options.deny_warnings {
+        for &lint in Lint::ALL.iter().filter(|&&lint| error_log::level(lint) == Level::Warn) {
+            error_log::set_level(lint, Level::Deny);
+        }
+    }
+}
+
+//The settings for `dir`, exiting if its lox.toml can't be read
+fn discover_config(dir: std::io::Result<std::path::PathBuf>) -> Config {
+    match dir.and_then(Config::discover) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(64);
+        }
+    }
+}
+
+//The command line, on top of `config`, exiting on a usage error
+fn parse_args(config: &Config) -> (Command, RunOptions) {
+    match cli::parse(std::env::args().skip(1), config) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{message}\n\n{}", cli::USAGE);
+            //EX_USAGE, as in the book
+            std::process::exit(64);
+        }
+    }
+}
+
+fn exit_on_error(result: Result<()>) {
+    if let Err(e) = result {
+        //Lex errors were reported as they were found
+        if !matches!(e, LoxError::Lex) {
+            eprintln!("{e}");
+        }
+        std::process::exit(e.exit_code());
+    }
+}
+
+//Read a script, naming it in the error if it can't be read
+fn read_source(path: impl AsRef<std::path::Path>) -> Result<String> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path)
+        .map_err(|e| LoxError::Io(std::io::Error::new(e.kind(), format!("{}: {e}", path.display()))))
+}
+
+//Print a script with syntax highlighting
+fn highlight_script(path: &str, format: Format, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    print!("{}", highlight::render(script, format, &options.scan));
+    Ok(())
+}
+
+//Print a script minified. Nothing is printed if it has syntax errors.
+fn minify_script(path: &str, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    let minified = minify::minify(script, &options.scan);
+    lex_result()?;
+    println!("{minified}");
     Ok(())
 }
 
+//Package a script as a standalone executable at `out`. Scripts with syntax errors aren't packaged.
+fn package_script(path: &str, out: &str, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    Scanner::with_options(script.clone(), options.scan.clone()).scan_parallel();
+    lex_result()?;
+    package::package(&script, &options.scan, out)
+        .map_err(|e| LoxError::Io(std::io::Error::new(e.kind(), format!("{out}: {e}"))))
+}
+
+//Print the tokens of a script, straight from the scanner
+fn print_tokens(path: &str, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    Scanner::with_options(script, options.scan.clone()).scan_parallel()
+        .into_iter()
+        .for_each(|token| println!("{token:?}"));
+    lex_result()
+}
+
+//Report the errors and warnings in a script, without running it.
+fn lint_script(path: &str, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    Scanner::with_options(script, options.scan.clone()).scan_parallel();
+    lex_result()
+}
+
+//Locate the entry point of a project directory, and then run it
+fn run_project(dir: &str, options: &RunOptions) -> Result<()> {
+    let project = Project::locate(dir)?;
+    let script = read_source(project.entry_path())?;
+    run(script, options)
+}
+
 //Read a script to string, and then run it
-fn run_script(path: String) -> Result<()> {
-    let script = std::fs::read_to_string(path)?;
-    run(script)
+fn run_script(path: String, options: &RunOptions) -> Result<()> {
+    let script = read_source(path)?;
+    run(script, options)
+}
+
+//Read an entire program from stdin, and then run it
+//Piped programs are usually generated on the fly, so they aren't cached.
+fn run_stdin(options: &RunOptions) -> Result<()> {
+    let mut script = String::new();
+    std::io::stdin().read_to_string(&mut script)?;
+    run(script, &RunOptions { use_cache: false, ..options.clone() })
+}
+
+//Re-run a script every time it's modified, until the process is interrupted
+fn watch_script(path: &str, options: &RunOptions) -> Result<()> {
+    let mut last_modified = None;
+
+    loop {
+        //Editors often replace a file when saving it, so it may briefly not exist.
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        if modified.is_some() && modified != last_modified {
+            last_modified = modified;
+
+            //Clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+            match run_script(path.to_owned(), options) {
+                //Already reported
+                Ok(()) | Err(LoxError::Lex) => {},
+                Err(e) => eprintln!("{e}"),
+            }
+            println!("[Watching {path} for changes, ctrl+c to exit]");
+        }
+
+        std::thread::sleep(Duration::from_millis(250));
+    }
 }
 
 //Accept a single line of code at a time, executing as it's read
-fn start_repl() -> Result<()> {
+fn start_repl(options: &RunOptions, prompt: &str) -> Result<()> {
+    let options = RunOptions { use_cache: false, ..options.clone() };
     let stdin = std::io::stdin();
     let mut handle = stdin.lock();
 
     loop {
-        print!("> ");
+        print!("{prompt}");
         std::io::stdout().flush()?;
 
         let mut buffer = String::new();
-        handle.read_line(&mut buffer)?;
-        let buffer = buffer.trim().to_owned();
-        if &buffer == "/quit" {
+        //End of input (ctrl+d)
+        if handle.read_line(&mut buffer)? == 0 {
             break;
         }
+        let buffer = match buffer.trim() {
+            "/quit" => break,
+            //Multi-line code (a pasted class, say) is run as a whole, not line by line
+            "/paste" => {
+                eprintln!("[Paste mode: /end on its own line runs the code]");
+                read_paste(&mut handle)?
+            },
+            line => line.to_owned(),
+        };
 
-        run(buffer)?;
+        //A typo shouldn't end the session: the errors are reported, and the next line is read
+        match run(buffer, &options) {
+            Ok(()) | Err(LoxError::Lex) => {},
+            Err(e) => return Err(e),
+        }
     }
 
     Ok(())
 }
 
+//Read lines verbatim until a line holding just `/end`, or the end of input.
+fn read_paste(input: &mut impl BufRead) -> Result<String> {
+    let mut code = String::new();
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 || line.trim_end() == "/end" {
+            return Ok(code);
+        }
+        code += &line;
+    }
+}
+
 //Run the script in string form.
-fn run(script: String) -> Result<()> {
-    let scanner = Scanner::new(script);
-    let tokens = scanner.scan_tokens();
+fn run(script: String, options: &RunOptions) -> Result<()> {
+    error_log::reset();
+
+    let cached = options.use_cache.then(|| cache::load(&script, &options.scan)).flatten();
+    let tokens = match cached {
+        Some(tokens) => tokens,
+        None => {
+            let tokens = Scanner::with_options(script.clone(), options.scan.clone()).scan_parallel();
+            //Scripts with errors or warnings aren't cached, so they're reported on every run.
+            //That includes allowed lints, which a later run may not allow.
+            //Failing to write the cache isn't fatal: the script still runs.
+            if options.use_cache && !error_log::had_error() && !error_log::had_lint() {
+                let _ = cache::store(&script, &options.scan, &tokens);
+            }
+            tokens
+        }
+    };
 
     tokens.into_iter()
         .for_each(|token| println!("{token:?}"));
-    Ok(())
+    lex_result()
+}
+
+//Whether the last scan succeeded, as a Result
+fn lex_result() -> Result<()> {
+    match error_log::had_error() {
+        true => Err(LoxError::Lex),
+        false => Ok(()),
+    }
 }
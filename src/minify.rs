@@ -0,0 +1,65 @@
+use crate::{scanner::{ScanOptions, Scanner}, tokens::TTy};
+
+//Operators longer than one char. Tokens that would run together into one of these need a space between them.
+const OPERATORS: &[&str] = &["!=", "==", "<=", ">=", "..", "..=", "...", "?.", "??", "|>", "//"];
+
+/// Rewrites `source` without comments, and without whitespace the tokens don't need to stay apart.
+/// The result scans to the same tokens. Inferred semicolons are written out, since the line breaks
+/// they came from are gone. A shebang line is kept.
+pub(crate) fn minify(source: String, options: &ScanOptions) -> String {
+    let shebang = source.starts_with("#!");
+    let chars: Vec<char> = source.chars().collect();
+    let (tokens, trivia) = Scanner::with_options(source, options.clone()).scan_with_trivia();
+    //The scanner ends the shebang at the same line break it does, whichever kind that is
+    let mut out = match trivia.first() {
+        Some(trivia) if shebang => chars[trivia.span.clone()].iter().collect::<String>() + "\n",
+        _ => String::new(),
+    };
+
+    let mut prev = "";
+    for token in tokens.iter().filter(|token| *token.ty() != TTy::EOF) {
+        //Inferred semicolons are the only tokens without any text
+        let text = match token.lexeme() {
+            "" => ";",
+            lexeme => lexeme,
+        };
+        if needs_space(prev, text) {
+            out.push(' ');
+        }
+        out.push_str(text);
+        prev = text;
+    }
+    out
+}
+
+//Would writing `next` straight after `prev` scan differently?
+fn needs_space(prev: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (prev.chars().last(), next.chars().next()) else {
+        return false;
+    };
+
+    //Words run together: `var x`, `ret 1`
+    last.is_alphanumeric() && first.is_alphanumeric()
+        //`1 .5` would be a fraction. Numbers are the only tokens starting with a digit.
+        || prev.starts_with(|ch: char| ch.is_ascii_digit()) && next == "."
+        //`r "x"` would be a raw string
+        || prev == "r" && matches!(first, '"' | '#')
+        //`"" "x"` would open a triple-quoted string
+        || last == '"' && first == '"'
+        //`< =` would be `<=`, and `/ /` a comment
+        || OPERATORS.iter().any(|op| op.strip_prefix(prev).is_some_and(|rest| rest.starts_with(first)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::minify;
+    use crate::scanner::ScanOptions;
+
+    #[test]
+    fn shebang_ends_at_any_line_break() {
+        for line_break in ["\n", "\r\n", "\r", "\u{85}", "\u{2028}", "\u{2029}"] {
+            let source = format!("#!/usr/bin/env rlox{line_break}print 1;\n");
+            assert_eq!(minify(source, &ScanOptions::default()), "#!/usr/bin/env rlox\nprint 1;");
+        }
+    }
+}
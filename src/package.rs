@@ -0,0 +1,58 @@
+use std::{fs::File, io::{Read, Result, Seek, SeekFrom}, path::Path};
+
+use crate::{config::Config, error_log::{level, Level, Lint}, scanner::ScanOptions};
+
+//Ends every packaged app, after the settings and script and their lengths.
+const MAGIC: &[u8; 8] = b"rlox-app";
+//The lengths of the settings and the script (as little endian u64s), then MAGIC.
+const TRAILER_LEN: usize = 8 + 8 + MAGIC.len();
+
+/// Writes a copy of the running rlox to `out`, with `script`, the options it's scanned with and the current lint levels appended.
+/// Started, the copy runs the script instead of acting as rlox.
+pub(crate) fn package(script: &str, options: &ScanOptions, out: impl AsRef<Path>) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let settings = settings(options);
+
+    let mut bytes = std::fs::read(&exe)?;
+    bytes.extend_from_slice(settings.as_bytes());
+    bytes.extend_from_slice(script.as_bytes());
+    bytes.extend_from_slice(&(settings.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(script.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(MAGIC);
+    std::fs::write(&out, bytes)?;
+    //Keeps the executable bit
+    std::fs::set_permissions(&out, std::fs::metadata(&exe)?.permissions())
+}
+
+/// The settings and script packaged into the running executable, if it's a packaged app.
+/// Only the end of the executable is read, so plain rlox starts as quickly as ever.
+pub(crate) fn embedded() -> Option<(Config, String)> {
+    let mut file = File::open(std::env::current_exe().ok()?).ok()?;
+    let mut trailer = [0; TRAILER_LEN];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64))).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if &trailer[16 ..] != MAGIC {
+        return None;
+    }
+
+    let settings_len = u64::from_le_bytes(trailer[.. 8].try_into().ok()?);
+    let script_len = u64::from_le_bytes(trailer[8 .. 16].try_into().ok()?);
+    let payload_len = i64::try_from(settings_len.checked_add(script_len)?).ok()?;
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64) - payload_len)).ok()?;
+
+    let (mut settings, mut script) = (String::new(), String::new());
+    file.by_ref().take(settings_len).read_to_string(&mut settings).ok()?;
+    file.take(script_len).read_to_string(&mut script).ok()?;
+    Some((Config::parse(&settings).ok()?, script))
+}
+
+//The scan options and lint levels, written as lox.toml settings so Config can read them back.
+fn settings(options: &ScanOptions) -> String {
+    let lints = |wanted: Level| Lint::ALL.iter().filter(|&&lint| level(lint) == wanted)
+        .map(|lint| format!("\"{}\"", lint.name())).collect::<Vec<_>>().join(", ");
+    format!(
+        "infer-semicolons = {}\nprint-fn = {}\nmax-string-len = {}\nmax-nesting = {}\ntab-width = {}\nallow = [{}]\nwarn = [{}]\ndeny = [{}]\n",
+        options.infer_semicolons, options.print_fn, options.max_string_len, options.max_nesting, options.tab_width,
+        lints(Level::Allow), lints(Level::Warn), lints(Level::Deny),
+    )
+}
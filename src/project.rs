@@ -0,0 +1,79 @@
+use std::{io::{Error, ErrorKind, Result}, path::{Path, PathBuf}};
+
+//The manifest file marking a project directory.
+pub(crate) const MANIFEST: &str = "lox.toml";
+//The entry point used when there's no manifest, or it doesn't name one.
+const DEFAULT_ENTRY: &str = "main.lox";
+
+//A directory of Lox code with a single entry point script.
+pub(crate) struct Project {
+    //The project directory. Relative paths in the manifest are based here.
+    root: PathBuf,
+    //The script to run, relative to root.
+    entry: PathBuf,
+}
+
+impl Project {
+    /// Locates the entry point of the project in `dir`.
+    /// A `lox.toml` with an `entry = "path"` key takes precedence over `main.lox`.
+    pub fn locate(dir: impl AsRef<Path>) -> Result<Self> {
+        let root = dir.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(Error::new(ErrorKind::NotFound, format!("{} is not a directory", root.display())));
+        }
+
+        let manifest = root.join(MANIFEST);
+        let entry = if manifest.is_file() {
+            let text = std::fs::read_to_string(&manifest)?;
+            manifest_string(&text, "entry").unwrap_or_else(|| DEFAULT_ENTRY.into())
+        } else {
+            DEFAULT_ENTRY.into()
+        };
+
+        let project = Self { root, entry: entry.into() };
+        if !project.entry_path().is_file() {
+            return Err(Error::new(ErrorKind::NotFound, format!("entry point {} not found", project.entry_path().display())));
+        }
+
+        Ok(project)
+    }
+
+    /// The full path of the script to run.
+    pub fn entry_path(&self) -> PathBuf {
+        self.root.join(&self.entry)
+    }
+}
+
+//Reads a top level `key = "value"` string from a manifest.
+fn manifest_string(text: &str, key: &str) -> Option<String> {
+    //Anything after the closing quote (like a # comment) is ignored.
+    let (value, _) = manifest_value(text, key)?.strip_prefix('"')?.split_once('"')?;
+    Some(value.to_owned())
+}
+
+/// Reads the raw text of a top level `key = value` from a manifest, with any comment removed.
+/// Only the small subset of TOML the manifest needs is understood.
+pub(crate) fn manifest_value<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    for line in text.lines() {
+        let line = line.trim();
+        //Keys inside a [table] don't belong to the top level.
+        if line.starts_with('[') {
+            return None;
+        }
+
+        let Some((k, v)) = line.split_once('=') else {
+            continue;
+        };
+
+        if k.trim() == key {
+            let v = v.trim();
+            //Strings may contain a #, so they're left for the caller to cut short
+            return Some(match v.starts_with('"') {
+                true => v,
+                false => v.split_once('#').map_or(v, |(v, _)| v.trim_end()),
+            });
+        }
+    }
+
+    None
+}
@@ -0,0 +1,70 @@
+use std::io::{BufRead, Write};
+
+use crate::{cli::RunOptions, error_log::{self, Diagnostic}, json::Json, scanner::Scanner, Result};
+
+/// The REPL's machine protocol, `rlox repl --protocol=jsonl`, for notebook-style frontends.
+/// Each line of stdin is a request, `{"input": "print 1;"}`, optionally with an `"id"` to echo back.
+/// Each gets a response line with the `execution_count`, `input`, `value`, `stdout`, `diagnostics`
+/// and `status` ("ok" or "error"). A request that can't be read gets a response with just the
+/// status and an `error` message, and doesn't count as an execution.
+pub(crate) fn serve(options: &RunOptions) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    let mut execution_count = 0;
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = Json::parse(&line);
+        let input = match request.as_ref().map(|request| request.get("input").and_then(Json::as_str)) {
+            Ok(Some(input)) => input.to_owned(),
+            Ok(None) => {
+                writeln!(stdout, "{}", invalid(request.as_ref().ok(), "Expected an object with an \"input\" string"))?;
+                continue;
+            },
+            Err(e) => {
+                writeln!(stdout, "{}", invalid(None, &format!("Invalid JSON: {e}")))?;
+                continue;
+            },
+        };
+        execution_count += 1;
+
+        error_log::reset();
+        let (tokens, diagnostics) = error_log::capture(|| Scanner::with_options(input.clone(), options.scan.clone()).scan_parallel());
+        //Until there's an interpreter, running code means printing its tokens, as the REPL does
+        let output: String = tokens.iter().map(|token| format!("{token:?}\n")).collect();
+
+        let mut response = id(request.as_ref().ok());
+        response.extend([
+            ("execution_count".into(), Json::Number(execution_count as f64)),
+            ("input".into(), Json::String(input)),
+            ("value".into(), Json::Null),
+            ("stdout".into(), Json::String(output)),
+            ("diagnostics".into(), Json::Array(diagnostics.iter().map(Diagnostic::to_json).collect())),
+            ("status".into(), Json::String(if error_log::had_error() { "error" } else { "ok" }.into())),
+        ]);
+        writeln!(stdout, "{}", Json::Object(response))?;
+        //Frontends wait for each response before sending the next request
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+//The response to a request that couldn't be read
+fn invalid(request: Option<&Json>, message: &str) -> Json {
+    let mut response = id(request);
+    response.push(("status".into(), Json::String("error".into())));
+    response.push(("error".into(), Json::String(message.into())));
+    Json::Object(response)
+}
+
+//The request's id, to start its response with, if it has one
+fn id(request: Option<&Json>) -> Vec<(String, Json)> {
+    request.and_then(|request| request.get("id"))
+        .map(|id| ("id".to_owned(), id.clone()))
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,150 @@
+use crate::{error_log::{warning, Lint}, suggest::closest, tokens::TTy};
+
+use super::Scanner;
+
+//Reserved words, and the token types they're scanned as.
+//True and False are intentionally proper cased- I think it looks better.
+const KEYWORDS: &[(&str, TTy)] = {
+    use TTy::*;
+    &[
+        ("and", And), ("class", Class), ("const", Const), ("defer", Defer), ("else", Else), ("enum", Enum),
+        ("for", For), ("fn", Fn), ("if", If), ("is", Is), ("null", Null), ("or", Or), ("print", Print),
+        ("ret", Return), ("super", Super), ("self", This), ("var", Var), ("while", While), ("yield", Yield),
+        ("True", True), ("False", False),
+    ]
+};
+
+impl Scanner {
+    //The reserved words in this dialect: all of KEYWORDS, except `print` when options.print_fn makes it a function.
+    pub(super) fn keywords(&self) -> impl Iterator<Item = &(&'static str, TTy)> + Clone {
+        KEYWORDS.iter().filter(|(_, ty)| !(self.options.print_fn && *ty == TTy::Print))
+    }
+
+    //Warns about identifiers that look like misspelled keywords: `whille (x) {`, `prnt "hi";`, `true`.
+    //Plenty of good names are one edit from a keyword (whale, car, elsa), so this only fires
+    //where an identifier can't be valid but the keyword would be. Runs over the finished tokens.
+    pub(super) fn warn_keyword_typos(&self) {
+        use TTy::*;
+
+        let closing_parens = self.closing_parens();
+        //For each open brace: is it a class body? Methods there look like `name(params) {`.
+        let mut braces: Vec<bool> = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.ty() {
+                //`class Name {` or `class Name < Super {`
+                LBrace => braces.push(i >= 2 && matches!(self.tokens[i - 2].ty(), Class | Lt)),
+                RBrace => { braces.pop(); },
+                Ident => self.warn_keyword_typo(i, braces.last() == Some(&true), &closing_parens),
+                _ => {}
+            }
+        }
+    }
+
+    //For each token, the index of the `)` closing it if it's a `(` that's closed
+    fn closing_parens(&self) -> Vec<Option<usize>> {
+        let mut closing = vec![None; self.tokens.len()];
+        let mut open: Vec<usize> = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.ty() {
+                TTy::LParen => open.push(i),
+                TTy::RParen => if let Some(start) = open.pop() {
+                    closing[start] = Some(i);
+                },
+                _ => {}
+            }
+        }
+        closing
+    }
+
+    fn warn_keyword_typo(&self, i: usize, in_class_body: bool, closing_parens: &[Option<usize>]) {
+        let token = &self.tokens[i];
+        let word = token.lexeme();
+        let keywords = self.keywords().map(|(keyword, _)| *keyword);
+
+        //Wrong case is always a mistake: `true` is not `True`
+        let suggestion = match keywords.clone().find(|keyword| keyword.eq_ignore_ascii_case(word)) {
+            Some(keyword) => keyword,
+            None => match closest(word, keywords.filter(|keyword| keyword.len() >= 3), 1) {
+                Some(keyword) if self.keyword_expected(i, in_class_body, closing_parens) => keyword,
+                _ => return,
+            },
+        };
+
+        let message = format!("Unknown identifier `{word}`. Did you mean `{suggestion}`?");
+        warning(&self.index, token.span().start - self.offset, Lint::KeywordTypo, message);
+    }
+
+    //Is the identifier at tokens[i] followed by something only a keyword could be?
+    fn keyword_expected(&self, i: usize, in_class_body: bool, closing_parens: &[Option<usize>]) -> bool {
+        use TTy::*;
+
+        let ty_at = |idx: usize| self.tokens.get(idx).map(|token| token.ty());
+        let prev = i.checked_sub(1).and_then(ty_at);
+        match ty_at(i + 1) {
+            //Two primaries in a row: `prnt "hi"`, `vra x`, `rett 1`
+            Some(Ident | String | Number | Char | True | False | Null | This) => true,
+            //A block straight after an identifier: `esle {`, though not `class A < B {`, `class A {` or `enum A {`
+            Some(LBrace) => !matches!(prev, Some(Lt | Class | Enum)),
+            //`whille (x) {`. Not a function (`fn name(a) {`) or a method, which look the same.
+            Some(LParen) if prev != Some(&Fn) && !in_class_body =>
+                closing_parens[i + 1].is_some_and(|close| ty_at(close + 1) == Some(&LBrace)),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error_log::capture, scanner::{ScanOptions, Scanner}};
+
+    //The keyword-typo warnings for `source`
+    fn typos(source: &str) -> Vec<String> {
+        let (_, diagnostics) = capture(|| Scanner::with_options(source.into(), ScanOptions::default()).scan_tokens());
+        diagnostics.into_iter().filter(|diagnostic| diagnostic.code.as_deref() == Some("keyword-typo"))
+            .map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn primary_after_an_identifier() {
+        assert_eq!(typos("prnt \"hi\";"), ["Unknown identifier `prnt`. Did you mean `print`?"]);
+        assert_eq!(typos("vra x = 1;"), ["Unknown identifier `vra`. Did you mean `var`?"]);
+        assert_eq!(typos("fn f() { rett 1; }"), ["Unknown identifier `rett`. Did you mean `ret`?"]);
+        //A name one edit from a keyword, used as a name
+        assert!(typos("var car = 1; print car; whale = car + 1;").is_empty());
+    }
+
+    #[test]
+    fn block_after_an_identifier() {
+        assert_eq!(typos("if (x) {} esle {}"), ["Unknown identifier `esle`. Did you mean `else`?"]);
+        assert_eq!(typos("whille (x) { }"), ["Unknown identifier `whille`. Did you mean `while`?"]);
+        assert_eq!(typos("whille (f(x) and (y)) { }"), ["Unknown identifier `whille`. Did you mean `while`?"]);
+        //A call isn't followed by a block
+        assert!(typos("whale(x); whale((x)) + 1; whale(").is_empty());
+        assert!(typos("class Elsa {} class Whale < Elsa {} enum Fore { A }").is_empty());
+    }
+
+    #[test]
+    fn wrong_case_always_warns() {
+        assert_eq!(typos("var done = true;"), ["Unknown identifier `true`. Did you mean `True`?"]);
+        assert_eq!(typos("WHILE = 1;"), ["Unknown identifier `WHILE`. Did you mean `while`?"]);
+        assert!(typos("var done = True;").is_empty());
+    }
+
+    #[test]
+    fn functions_and_methods_look_like_blocks() {
+        assert!(typos("fn whale(x) { ret x; }").is_empty());
+        assert!(typos("class Sea { whale(x) { ret x; } fore() {} }").is_empty());
+        assert!(typos("class Sea < Lake { whale(x) { ret x; } }").is_empty());
+        //Outside the class body, it's a block again
+        assert_eq!(typos("class Sea { whale() {} } whille (x) {}"), ["Unknown identifier `whille`. Did you mean `while`?"]);
+        //Inside a method body too
+        assert_eq!(typos("class Sea { f() { whille (x) {} } }"), ["Unknown identifier `whille`. Did you mean `while`?"]);
+    }
+
+    #[test]
+    fn unclosed_parens_are_linear() {
+        //Each `whale(` used to scan forward to the end for its `)`
+        let source = "whale(".repeat(100_000);
+        assert!(typos(&source).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{error_log::{warning, Lint}, tokens::TTy};
+
+use super::Scanner;
+
+impl Scanner {
+    //Warns about comparisons written as a chain, `a < b < c`, which compares the bool from `a < b` with c.
+    //Without a parser, this looks for two comparisons with only operands and arithmetic between them,
+    //at the same bracket depth. Runs over the finished tokens.
+    pub(super) fn warn_chained_comparisons(&self) {
+        use TTy::*;
+
+        //For the top level and each open bracket: where the current expression began,
+        //and the comparisons in it so far.
+        let mut frames: Vec<(usize, Vec<usize>)> = vec![(0, Vec::new())];
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.ty() {
+                LParen | LBracket => frames.push((i + 1, Vec::new())),
+                RParen | RBracket | RBrace => {
+                    //An unmatched closer leaves the top level alone
+                    if frames.len() > 1 {
+                        if let Some((start, comparisons)) = frames.pop() {
+                            self.warn_chain(start, &comparisons, i);
+                        }
+                    }
+                    //A closed group is an operand, but a block ends the expression before it
+                    if *token.ty() == RBrace {
+                        self.end_expression(&mut frames, i);
+                    }
+                },
+                LBrace => {
+                    self.end_expression(&mut frames, i);
+                    frames.push((i + 1, Vec::new()));
+                },
+                Lt | LtEq | Gt | GtEq => frames.last_mut().into_iter().for_each(|(_, comparisons)| comparisons.push(i)),
+                Ident | String | Number | Char | True | False | Null | This | Super
+                | Plus | Minus | Asterisk | FSlash | Bang | Period | QuestionDot => {},
+                _ => self.end_expression(&mut frames, i),
+            }
+        }
+    }
+
+    //Warns about an assignment as the condition of an if or while, `if (x = 5)`, which is usually a mistyped `==`.
+    //Only `=` directly inside the condition's parens counts, so `if ((x = next()))` says it's intended.
+    pub(super) fn warn_assign_in_conditions(&self) {
+        use TTy::*;
+
+        //For each open bracket: is it a condition's parens, and has it been warned about?
+        let mut open: Vec<(bool, bool)> = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.ty() {
+                LParen => open.push((i > 0 && matches!(self.tokens[i - 1].ty(), If | While), false)),
+                LBracket | LBrace => open.push((false, false)),
+                RParen | RBracket | RBrace => { open.pop(); },
+                //Once per condition
+                Eq => if let Some((true, warned)) = open.last_mut() {
+                    if !*warned {
+                        *warned = true;
+                        let message = "Assignment used as a condition. Did you mean `==`? Wrap it in another pair of parentheses if the assignment is intended.";
+                        warning(&self.index, token.span().start - self.offset, Lint::AssignInCondition, message);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    //Checks the expression in the innermost frame, which ends before tokens[end], and starts a new one after it.
+    fn end_expression(&self, frames: &mut [(usize, Vec<usize>)], end: usize) {
+        if let Some((start, comparisons)) = frames.last_mut() {
+            self.warn_chain(*start, comparisons, end);
+            *start = end + 1;
+            comparisons.clear();
+        }
+    }
+
+    fn warn_chain(&self, start: usize, comparisons: &[usize], end: usize) {
+        let &[first, second, ..] = comparisons else {
+            return;
+        };
+        let (left, mid, right) = (
+            self.text(start .. first),
+            self.text(first + 1 .. second),
+            self.text(second + 1 .. comparisons.get(2).copied().unwrap_or(end)),
+        );
+        //Something is missing, which is a syntax error rather than a chain
+        if left.is_empty() || mid.is_empty() || right.is_empty() {
+            return;
+        }
+
+        let (op1, op2) = (self.tokens[first].lexeme(), self.tokens[second].lexeme());
+        let message = format!("Comparisons don't chain: this compares the bool `{left} {op1} {mid}` with `{right}`. Did you mean `{left} {op1} {mid} and {mid} {op2} {right}`?");
+        warning(&self.index, self.tokens[second].span().start - self.offset, Lint::ChainedComparison, message);
+    }
+
+    //The source text of tokens[range], including anything between them.
+    fn text(&self, range: std::ops::Range<usize>) -> String {
+        match (self.tokens.get(range.start), range.end.checked_sub(1).and_then(|last| self.tokens.get(last))) {
+            (Some(first), Some(last)) if range.start < range.end => {
+                self.buf[first.span().start - self.offset .. last.span().end - self.offset].iter().collect()
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error_log::{capture, Lint}, scanner::{ScanOptions, Scanner}};
+
+    //The messages of the warnings `lint` gives for `source`
+    fn warnings(source: &str, lint: Lint) -> Vec<String> {
+        let (_, diagnostics) = capture(|| Scanner::with_options(source.into(), ScanOptions::default()).scan_tokens());
+        diagnostics.into_iter().filter(|diagnostic| diagnostic.code.as_deref() == Some(lint.name()))
+            .map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn chained_comparisons_warn() {
+        assert_eq!(warnings("print a < b < c;", Lint::ChainedComparison),
+            ["Comparisons don't chain: this compares the bool `a < b` with `c`. Did you mean `a < b and b < c`?"]);
+        assert_eq!(warnings("if (0 <= x + 1 > y.z) {}", Lint::ChainedComparison),
+            ["Comparisons don't chain: this compares the bool `0 <= x + 1` with `y.z`. Did you mean `0 <= x + 1 and x + 1 > y.z`?"]);
+        //Inside brackets, and in each of two statements
+        assert_eq!(warnings("f(a < b < c); xs[a > b > c];", Lint::ChainedComparison).len(), 2);
+    }
+
+    #[test]
+    fn separate_comparisons_dont_warn() {
+        for source in [
+            "print (a < b) < c;",
+            "print a < b and b < c;",
+            "print a < b; print b < c;",
+            "f(a < b, b < c);",
+            "class A < B { f() { ret x < y; } }",
+            "for (var i = 0; i < n; i = i + 1) print i < n;",
+            "if (a < b) { c < d; }",
+            //Missing operands are a syntax error, not a chain
+            "print < a < b;",
+        ] {
+            assert!(warnings(source, Lint::ChainedComparison).is_empty(), "{source}");
+        }
+    }
+
+    #[test]
+    fn assign_in_conditions_warn() {
+        let message = "Assignment used as a condition. Did you mean `==`? Wrap it in another pair of parentheses if the assignment is intended.";
+        assert_eq!(warnings("if (x = 5) print x;", Lint::AssignInCondition), [message]);
+        assert_eq!(warnings("while (line = next()) print line;", Lint::AssignInCondition), [message]);
+        //Once per condition
+        assert_eq!(warnings("if (x = 1 and y = 2) {} if (a.b = c) {}", Lint::AssignInCondition).len(), 2);
+        //Inside a condition nested in another one's block
+        assert_eq!(warnings("while (x) { if (y = 1) {} }", Lint::AssignInCondition).len(), 1);
+    }
+
+    #[test]
+    fn other_assignments_dont_warn() {
+        for source in [
+            "if (x == 5) print x;",
+            "while ((line = next())) print line;",
+            "if (f(x = 1)) {}",
+            "if ([x = 1]) {}",
+            "if (x) y = 1;",
+            "if (x) { y = 1; }",
+            "for (i = 0; i < n; i = i + 1) {}",
+            "x = iffy(y = 1);",
+        ] {
+            assert!(warnings(source, Lint::AssignInCondition).is_empty(), "{source}");
+        }
+    }
+
+    #[test]
+    fn unclosed_conditions_are_linear() {
+        //Each `if(` used to scan forward to the end for its `)`
+        let source = "if(".repeat(100_000) + "x = 1";
+        //Only the innermost has `x = 1` directly inside
+        assert_eq!(warnings(&source, Lint::AssignInCondition).len(), 1);
+    }
+}
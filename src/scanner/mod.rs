@@ -1,4 +1,19 @@
-use crate::{tokens::{Token, TTy, TLit}, error_log::error};
+use std::num::NonZeroUsize;
+
+use crate::{tokens::{locate_bytes, Token, TTy, TLit, Trivia, TriviaKind}, error_log::{error, error_with_notes, warning, ErrorCode, Lint}, line_index::{LineIndex, line_break_len}};
+
+mod keywords;
+mod lints;
+mod options;
+mod relex;
+mod semicolons;
+
+pub(crate) use options::ScanOptions;
+#[cfg_attr(not(test), allow(unused_imports))]
+pub(crate) use relex::TextEdit;
+
+//Sources shorter than this many chars per thread aren't worth splitting up.
+const MIN_PARALLEL_CHUNK: usize = 1 << 16;
 
 //Represents a lexer for the language, maintaining position and spans
 //within the provided source code. The only public method on this struct
@@ -14,37 +29,330 @@ pub(crate) struct Scanner {
     start: usize,
     //Marks the current position, or the end, of the current span.
     current: usize,
-    //The current line number of the script.
-    //Has no meaning in context of the read tokens, and is only
-    //used for error reporting.
-    line: usize,
+    //Line start offsets of the script, used to give tokens and errors a line.
+    //Handles CRLF and Unicode line breaks, so the scanner never counts lines itself.
+    index: LineIndex,
     //Holds the list of already parsed tokens.
     tokens: Vec<Token>,
+    //Comments skipped while scanning, in source order.
+    trivia: Vec<Trivia>,
+    //Where buf begins within the whole script, added to every token span.
+    //Only non-zero for chunks scanned by scan_parallel.
+    offset: usize,
+    options: ScanOptions,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
+    pub fn with_options(source: String, options: ScanOptions) -> Self {
+        Self::chunk(source.chars().collect(), 1, 0, options)
+    }
+
+    //A scanner for a piece of a larger script which starts on line `first_line`,
+    //`offset` chars into the script.
+    fn chunk(buf: Vec<char>, first_line: usize, offset: usize, options: ScanOptions) -> Self {
         Self {
             start: 0,
             current: 0,
-            line: 1,
-            buf: source.chars().collect(),
+            index: LineIndex::starting_at(&buf, first_line, options.tab_width),
+            buf,
             tokens: Vec::new(),
+            trivia: Vec::new(),
+            offset,
+            options,
         }
     }
 
     /// Consumes the source code from start to finish,
     /// yielding the complete list of lexed tokens.
-    pub fn scan_tokens(mut self) -> Vec<Token> {
+    pub fn scan_tokens(self) -> Vec<Token> {
+        self.scan_with_trivia().0
+    }
+
+    /// Like scan_tokens, but also yields the trivia (comments, whitespace and rejected text) between tokens.
+    /// Tools that care about the exact source, like the doc generator, use this.
+    pub fn scan_with_trivia(mut self) -> (Vec<Token>, Vec<Trivia>) {
+        self.skip_shebang();
+        self.scan_all();
+        self.finish();
+        //Consumes self, effectively mapping Scanner to its tokens and trivia
+        (self.tokens, self.trivia)
+    }
+
+    /// Like scan_tokens, but large sources are split into chunks which are
+    /// scanned on separate threads. The resulting tokens are identical.
+    pub fn scan_parallel(self) -> Vec<Token> {
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        self.scan_chunked(threads, MIN_PARALLEL_CHUNK)
+    }
+
+    //scan_parallel, splitting the source into chunks of at least `min_chunk` chars for up to `threads` threads.
+    fn scan_chunked(self, threads: usize, min_chunk: usize) -> Vec<Token> {
+        let bounds = self.chunk_bounds(threads, min_chunk);
+        //Not enough source (or no safe places) to split it
+        if bounds.len() <= 2 {
+            return self.scan_tokens();
+        }
+
+        let chunks: Vec<(Vec<Token>, Vec<Trivia>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = bounds.windows(2)
+                .map(|span| {
+                    let (start, end) = (span[0], span[1]);
+                    //Each chunk indexes its own lines, starting from where it sits in the
+                    //whole script, so its tokens need no re-basing afterwards.
+                    let mut scanner = Scanner::chunk(self.buf[start .. end].to_vec(), self.index.line_of(start), start, self.options.clone());
+                    scope.spawn(move || {
+                        //Only the real start of the script can have a shebang.
+                        if start == 0 {
+                            scanner.skip_shebang();
+                        }
+                        scanner.scan_all();
+                        (scanner.tokens, scanner.trivia)
+                    })
+                })
+                .collect();
+
+            handles.into_iter()
+                .map(|handle| handle.join().expect("scanner thread panicked"))
+                .collect()
+        });
+
+        let mut scanner = self;
+        for (tokens, trivia) in chunks {
+            scanner.tokens.extend(tokens);
+            scanner.trivia.extend(trivia);
+        }
+        scanner.current = scanner.buf.len();
+        scanner.finish();
+        scanner.tokens
+    }
+
+    //The passes over the finished tokens, once the scanner is at the end.
+    fn finish(&mut self) {
+        //Manually insert the EOF marker
+        self.push_eof();
+        if self.options.infer_semicolons {
+            self.infer_semicolons();
+        }
+        locate_bytes(&mut self.tokens, &self.buf);
+        self.check_tokens();
+        self.warn_mixed_indent();
+        if self.options.check_round_trip {
+            self.check_round_trip();
+        }
+    }
+
+    //The checks that only need the whole token list, which relex runs too.
+    fn check_tokens(&self) {
+        self.check_nesting();
+        self.warn_keyword_typos();
+        self.warn_chained_comparisons();
+        self.warn_assign_in_conditions();
+    }
+
+    fn push_eof(&mut self) {
+        let end = self.offset + self.current;
+        self.tokens.push(Token::new(TTy::EOF, "", TLit::Null, self.line(), end .. end));
+    }
+
+    //Reports the first bracket nested deeper than options.max_nesting.
+    //Checked over the finished tokens, since parallel chunks can't know their starting depth.
+    fn check_nesting(&self) {
+        let mut depth: usize = 0;
+        for token in &self.tokens {
+            match token.ty() {
+                TTy::LParen | TTy::LBracket | TTy::LBrace => depth += 1,
+                TTy::RParen | TTy::RBracket | TTy::RBrace => depth = depth.saturating_sub(1),
+                _ => continue,
+            }
+
+            if depth > self.options.max_nesting {
+                let message = format!("Brackets nested too deeply (the limit is {}).", self.options.max_nesting);
+                error(&self.index, token.span().start - self.offset, ErrorCode::NestingTooDeep, message);
+                return;
+            }
+        }
+    }
+
+    //Warns about lines indented with both tabs and spaces, or with the other one from the first indented line.
+    //Only whitespace trivia is looked at, so lines inside multi-line strings are left alone.
+    fn warn_mixed_indent(&self) {
+        //The char the first indented line used
+        let mut style = None;
+        for trivia in self.trivia.iter().filter(|trivia| trivia.kind == TriviaKind::Whitespace) {
+            let (mut i, end) = (trivia.span.start - self.offset, trivia.span.end - self.offset);
+            let mut at_line_start = i == 0 || line_break_len(&self.buf, i - 1).is_some();
+            while i < end {
+                if let Some(len) = line_break_len(&self.buf, i) {
+                    i += len;
+                    at_line_start = true;
+                    continue;
+                }
+                if !at_line_start {
+                    i += 1;
+                    continue;
+                }
+
+                let indent_end = (i .. end).find(|&j| !matches!(self.buf[j], ' ' | '\t')).unwrap_or(end);
+                let indent = &self.buf[i .. indent_end];
+                //Blank lines don't count, whatever whitespace they hold
+                let blank = indent_end == self.buf.len() || line_break_len(&self.buf, indent_end).is_some();
+                let message = match (indent.contains(&' '), indent.contains(&'\t')) {
+                    _ if blank => None,
+                    (true, true) => Some("Indentation mixes tabs and spaces.".to_owned()),
+                    (true, false) | (false, true) => {
+                        let ch = indent[0];
+                        match *style.get_or_insert(ch) {
+                            first if first != ch => Some(format!("Indented with {}, but earlier lines use {}.", indent_name(ch), indent_name(first))),
+                            _ => None,
+                        }
+                    },
+                    (false, false) => None,
+                };
+                if let Some(message) = message {
+                    warning(&self.index, i, Lint::MixedIndent, message);
+                }
+                i = indent_end;
+                at_line_start = false;
+            }
+        }
+    }
+
+    //Reports the first place where the tokens and trivia don't reproduce the source exactly:
+    //a gap or overlap between them, or a lexeme or byte range that doesn't match its span.
+    fn check_round_trip(&self) {
+        let tokens = self.tokens.iter()
+            .filter(|token| !token.span().is_empty())
+            .map(|token| (token.span(), Some(token)));
+        let mut pieces: Vec<_> = tokens.chain(self.trivia.iter().map(|trivia| (trivia.span.clone(), None))).collect();
+        pieces.sort_by_key(|(span, _)| span.start);
+
+        let mut pos = self.offset;
+        for (span, token) in pieces {
+            let text: String = self.buf[span.start - self.offset .. span.end - self.offset].iter().collect();
+            let mismatch = span.start != pos
+                || token.is_some_and(|token| token.lexeme() != text || token.source_range().len() != text.len());
+            if mismatch {
+                error(&self.index, pos.min(span.start) - self.offset, ErrorCode::ScannerBug, "Scanner bug: the tokens and trivia here don't match the source.");
+                return;
+            }
+            pos = span.end;
+        }
+
+        if pos != self.offset + self.buf.len() {
+            error(&self.index, pos - self.offset, ErrorCode::ScannerBug, "Scanner bug: the tokens and trivia end before the source does.");
+        }
+    }
+
+    //Scans tokens until the end of the buffer.
+    fn scan_all(&mut self) {
         while !self.reached_eof() {
-            self.start = self.current;
-            self.scan_token();
+            self.scan_next();
         }
+    }
 
-        //Manually insert the EOF marker once the scanner is at the end.
-        self.tokens.push(Token::new(TTy::EOF, "", TLit::Null, self.line));
-        //Consumes self, effectively mapping Scanner to Vec<Token>
-        self.tokens
+    //Scans the token at the current position.
+    //Text that made neither a token nor trivia was rejected (and reported), so it's kept as Skipped trivia.
+    fn scan_next(&mut self) {
+        self.start = self.current;
+        //Trivia may have grown by merging, rather than by a new piece
+        let found = |scanner: &Self| (scanner.tokens.len(), scanner.trivia.last().map(|trivia| trivia.span.clone()));
+        let before = found(self);
+        self.scan_token();
+        if found(self) == before {
+            self.push_trivia(TriviaKind::Skipped);
+        }
+    }
+
+    //Records the current span as trivia. Runs of whitespace (or rejected text) are merged into one piece.
+    fn push_trivia(&mut self, kind: TriviaKind) {
+        let span = self.offset + self.start .. self.offset + self.current;
+        match self.trivia.last_mut() {
+            Some(last) if last.kind == kind && last.span.end == span.start && matches!(kind, TriviaKind::Whitespace | TriviaKind::Skipped) => {
+                last.span.end = span.end;
+            },
+            _ => self.trivia.push(Trivia { kind, span }),
+        }
+    }
+
+    //Finds offsets to split the buffer at for scan_parallel, aiming for one chunk per thread.
+    //Returned offsets always include 0 and the end of the buffer.
+    //A split is only safe right after a line break that isn't inside a string or comment,
+    //since no token (or piece of trivia) can span it.
+    fn chunk_bounds(&self, threads: usize, min_chunk: usize) -> Vec<usize> {
+        let chunk_len = (self.buf.len() / threads).max(min_chunk);
+        let mut bounds = vec![0];
+        //Inside a string: its kind, which says what closes it
+        let mut in_string: Option<StringKind> = None;
+        //A shebang line behaves like a comment.
+        let mut in_comment = self.buf.starts_with(&['#', '!']);
+        //Inside an identifier, where an `r` can't start a raw string
+        let mut in_ident = false;
+
+        let mut i = 0;
+        while i < self.buf.len() {
+            if let Some(len) = line_break_len(&self.buf, i) {
+                i += len;
+                in_comment = false;
+                in_ident = false;
+                //Ordinary strings end with their line, closed or not
+                if in_string == Some(StringKind::Plain) {
+                    in_string = None;
+                }
+                if in_string.is_none() && i - bounds[bounds.len() - 1] >= chunk_len && i < self.buf.len() {
+                    bounds.push(i);
+                }
+                continue;
+            }
+
+            let ch = self.buf[i];
+            match in_string {
+                Some(kind) => if let Some(len) = kind.closing_len(&self.buf, i) {
+                    in_string = None;
+                    i += len - 1;
+                },
+                None if in_comment => {},
+                None => match ch {
+                    '"' if self.buf[i + 1 ..].starts_with(&['"', '"']) => {
+                        in_string = Some(StringKind::Triple);
+                        i += 2;
+                    },
+                    '"' => in_string = Some(StringKind::Plain),
+                    'r' if !in_ident => if let Some(hashes) = raw_string_hashes(&self.buf, i + 1) {
+                        in_string = Some(StringKind::Raw(hashes));
+                        //Skip the hashes and the opening quote
+                        i += hashes + 1;
+                    },
+                    //Char literals never span lines, but may hold a quote: '"'
+                    '\'' => {
+                        let end = char_literal_end(&self.buf, i + 1);
+                        //Carry on from the closing quote, or from just before the line break that left it unterminated
+                        i = if self.buf.get(end) == Some(&'\'') { end } else { end - 1 };
+                    },
+                    '/' if self.buf.get(i + 1) == Some(&'/') => in_comment = true,
+                    _ => {}
+                },
+            }
+            in_ident = in_string.is_none() && !in_comment && if in_ident { ch.is_alphanumeric() } else { ch.is_alphabetic() };
+            i += 1;
+        }
+
+        bounds.push(self.buf.len());
+        bounds
+    }
+
+    //Executable scripts may start with `#!/usr/bin/env rlox`.
+    //Only the shebang itself is skipped: the buffer is left untouched and the
+    //line break is scanned as usual, so offsets and line numbers stay correct.
+    //It's kept as comment trivia.
+    fn skip_shebang(&mut self) {
+        if !self.buf.starts_with(&['#', '!']) {
+            return;
+        }
+
+        while !self.at_line_break() && !self.reached_eof() {
+            self.advance();
+        }
+        self.trivia.push(Trivia { kind: TriviaKind::Comment, span: self.offset .. self.offset + self.current });
     }
 
     fn reached_eof(&self) -> bool {
@@ -65,8 +373,18 @@ impl Scanner {
             ')' => RParen,
             '{' => LBrace,
             '}' => RBrace,
+            '[' => LBracket,
+            ']' => RBracket,
             ',' => Comma,
-            '.' => Period,
+            //Could be a Period, either of the range operators (`..` or `..=`),
+            //or an Ellipsis. Longest match wins.
+            '.' => match self.expect_many(&['.', '.'], Ellipsis, Period) {
+                Period => match self.expect_many(&['.', '='], DotDotEq, Period) {
+                    Period => self.expect_many(&['.'], DotDot, Period),
+                    ty => ty,
+                },
+                ty => ty,
+            },
             '-' => Minus,
             '+' => Plus,
             ';' => Semicolon,
@@ -77,37 +395,81 @@ impl Scanner {
             '=' => self.expect_many(&['='], EqEq, Eq),
             '<' => self.expect_many(&['='], LtEq, Lt),
             '>' => self.expect_many(&['='], GtEq, Gt),
+            //Null-safety operators. A lone '?' isn't valid.
+            '?' => {
+                let ty = match self.expect_many(&['.'], QuestionDot, Null) {
+                    Null => self.expect_many(&['?'], QuestionQuestion, Null),
+                    ty => ty,
+                };
+                if ty == Null {
+                    error(&self.index, self.start, ErrorCode::UnexpectedChar, "Unexpected char.");
+                    return;
+                }
+
+                ty
+            }
+            //The pipeline operator. A lone '|' isn't valid.
+            '|' => {
+                if self.expect_many(&['>'], Pipe, Null) == Null {
+                    error(&self.index, self.start, ErrorCode::UnexpectedChar, "Unexpected char.");
+                    return;
+                }
+
+                Pipe
+            }
             //Could potentially be a FSlash or a line comment.
             '/' => {
                 //If expect_many returns Null for this, the current buffer is ['/', '/'],
                 //AKA it's a line comment. Otherwise, it's an FSlash.
                 let ty = self.expect_many(&['/'], Null, FSlash);
                 if ty == Null {
-                    //Consume the buffer until we reach a newline, ending the line comment.
-                    while self.peek() != '\n' && !self.reached_eof() {
+                    //Exactly three slashes make a doc comment. `////` is just a comment.
+                    let kind = match (self.peek(), self.peek_ahead(1)) {
+                        ('/', '/') => TriviaKind::Comment,
+                        ('/', _) => TriviaKind::DocComment,
+                        _ => TriviaKind::Comment,
+                    };
+
+                    //Consume the buffer until we reach a line break, ending the line comment.
+                    while !self.at_line_break() && !self.reached_eof() {
                         self.advance();
                     }
-                    //Discard everything we read; Comments are not useful
+                    //Comments aren't tokens, but tools may want them
+                    self.push_trivia(kind);
                     return
                 }
 
                 FSlash
             }
             '"' => {
-                self.expect_string();
+                match self.buf[self.current ..].starts_with(&['"', '"']) {
+                    true => self.expect_triple_string(),
+                    false => self.expect_string(),
+                }
+                return;
+            },
+            '\'' => {
+                self.expect_char();
+                return;
+            },
+            //Skip whitespace, including line breaks: lines are tracked by self.index.
+            //A CR is only whitespace here because CRLF and lone CRs are both breaks.
+            ' ' | '\t' | '\r' | '\n' | '\u{85}' | '\u{2028}' | '\u{2029}' => {
+                self.push_trivia(TriviaKind::Whitespace);
                 return;
             },
-            //Ignore whitespace
-            ' ' | '\r' | '\t' => return,
-            //Increment the line counter and continue
-            '\n' => {
-                self.line += 1;
-                return
-            }
             //Edge cases:
             _ => {
+                //Raw strings: r"..." or r#"..."#, with any number of #s
+                if ch == 'r' {
+                    if let Some(hashes) = raw_string_hashes(&self.buf, self.current) {
+                        self.expect_raw_string(hashes);
+                        return;
+                    }
+                }
+
                 //Parse numbers
-                if ch.is_digit(10) {
+                if ch.is_ascii_digit() {
                     self.expect_number();
                     return;
                 }
@@ -118,7 +480,7 @@ impl Scanner {
                 }
 
                 //Unhandled chars: report it and continue.
-                error(self.line, "Unexpected char.");
+                error(&self.index, self.start, ErrorCode::UnexpectedChar, "Unexpected char.");
                 return;
             }
         };
@@ -130,7 +492,7 @@ impl Scanner {
     //true => yes
     //false => no
     fn expect_many(&mut self, expected: &[char], yes: TTy, no: TTy) -> TTy {
-        if self.current + expected.len() >= self.buf.len() {
+        if self.current + expected.len() > self.buf.len() {
             return no;
         }
 
@@ -151,23 +513,29 @@ impl Scanner {
 
     //Consumes the buffer until a matching end quote (") is found.
     fn expect_string(&mut self) {
-        //While the end quote hasn't been found and we're not at the end
-        while self.peek() != '"' && !self.reached_eof() {
-            //Track newlines (meaning string literals are multiline enabled)
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
+        //While the end quote hasn't been found and we're not at the end of the line.
+        //Strings end with their line: multi-line strings are triple-quoted.
+        while self.peek() != '"' && !self.at_line_break() && !self.reached_eof() {
             self.advance();
         }
 
-        if self.reached_eof() {
-            error(self.line, "Unterminated string literal.");
+        //Scanning carries on from the line break, so one missing quote doesn't swallow the lines after it
+        if self.peek() != '"' || self.reached_eof() {
+            error_with_notes(&self.index, self.current, ErrorCode::UnterminatedString, "Unterminated string literal.", &[(self.start, "The string starts here.")]);
             return;
         }
 
         //Consume the end quote: it's not part of the string literal, it's just syntax.
         self.advance();
 
+        //The quotes don't count towards the length
+        let len = self.current - self.start - 2;
+        if len > self.options.max_string_len {
+            let message = format!("String literal is too long ({len} chars, the limit is {}).", self.options.max_string_len);
+            error(&self.index, self.start, ErrorCode::StringTooLong, message);
+            return;
+        }
+
         //Get the current span (includes the quotes), and then manually truncate them out.
         let lit: String = {
             let span = self.span_string();
@@ -176,22 +544,159 @@ impl Scanner {
         self.add_token_lit(TTy::String, TLit::String(lit));
     }
 
-    //Parses a f64 literal
+    //Consumes a triple-quoted string, after its first quote. These are the multi-line strings,
+    //laid out as strip_indent describes.
+    fn expect_triple_string(&mut self) {
+        //The rest of the opening quotes
+        self.current += 2;
+        while !self.buf[self.current ..].starts_with(&['"', '"', '"']) {
+            if self.reached_eof() {
+                //Point at the start, since the end of the file is rarely where the string was meant to end
+                error(&self.index, self.start, ErrorCode::UnterminatedString, "Unterminated multi-line string literal (expected `\"\"\"` to close it).");
+                return;
+            }
+            self.advance();
+        }
+        self.current += 3;
+
+        //The quotes don't count towards the length
+        let len = self.current - self.start - 6;
+        if len > self.options.max_string_len {
+            let message = format!("String literal is too long ({len} chars, the limit is {}).", self.options.max_string_len);
+            error(&self.index, self.start, ErrorCode::StringTooLong, message);
+            return;
+        }
+
+        let lit = strip_indent(&self.buf[self.start + 3 .. self.current - 3]);
+        self.add_token_lit(TTy::String, TLit::String(lit));
+    }
+
+    //A string left open reaches the end of the file, but was most likely meant to end on the line it started.
+    //Moves back to the end of that line, so the string is skipped only up to there, and the lines after it
+    //still produce tokens (and their own errors) as usual.
+    fn recover_unterminated_string(&mut self) {
+        self.current = (self.start .. self.buf.len())
+            .find(|&i| line_break_len(&self.buf, i).is_some())
+            .unwrap_or(self.buf.len());
+    }
+
+    //Consumes a raw string, after its `r`. Raw strings may contain quotes, as only a quote
+    //followed by as many #s as opened the string ends it: r#"say "hi""#
+    fn expect_raw_string(&mut self, hashes: usize) {
+        //The #s and the opening quote
+        self.current += hashes + 1;
+        let closing: Vec<char> = std::iter::once('"').chain(std::iter::repeat_n('#', hashes)).collect();
+
+        while !self.buf[self.current ..].starts_with(&closing) {
+            if self.reached_eof() {
+                self.recover_unterminated_string();
+                let message = format!("Unterminated raw string literal (expected `{}` to close it).", closing.iter().collect::<String>());
+                error_with_notes(&self.index, self.current, ErrorCode::UnterminatedString, message, &[(self.start, "The string starts here.")]);
+                return;
+            }
+            self.advance();
+        }
+        self.current += closing.len();
+
+        //The delimiters (r, #s and quotes) don't count towards the length
+        let delimiters = 1 + 2 * closing.len();
+        let len = self.current - self.start - delimiters;
+        if len > self.options.max_string_len {
+            let message = format!("String literal is too long ({len} chars, the limit is {}).", self.options.max_string_len);
+            error(&self.index, self.start, ErrorCode::StringTooLong, message);
+            return;
+        }
+
+        let lit: String = self.buf[self.start + 1 + closing.len() .. self.current - closing.len()].iter().collect();
+        self.add_token_lit(TTy::String, TLit::String(lit));
+    }
+
+    //Consumes a char literal: a single char or escape sequence between single quotes.
+    //Unlike strings, char literals can't span lines.
+    fn expect_char(&mut self) {
+        self.current = char_literal_end(&self.buf, self.current);
+        if self.peek() != '\'' {
+            error(&self.index, self.start, ErrorCode::UnterminatedChar, "Unterminated char literal.");
+            return;
+        }
+        self.advance();
+
+        let contents = &self.buf[self.start + 1 .. self.current - 1];
+        let value = match contents {
+            [] => Err("Empty char literal.".to_owned()),
+            ['\\', escape @ ..] => char_escape(escape)
+                .ok_or_else(|| format!("Unknown escape sequence `\\{}` in char literal.", escape.iter().collect::<String>())),
+            [ch] => Ok(*ch),
+            _ => Err(format!("Char literals hold a single char. Use a string for \"{}\".", contents.iter().collect::<String>())),
+        };
+
+        match value {
+            Ok(ch) => self.add_token_lit(TTy::Char, TLit::Char(ch)),
+            Err(message) => error(&self.index, self.start, ErrorCode::InvalidChar, message),
+        }
+    }
+
+    //Parses an integer or f64 literal
     fn expect_number(&mut self) {
-        while self.peek().is_digit(10) && !self.reached_eof() {
+        while self.peek().is_ascii_digit() && !self.reached_eof() {
             self.advance();
         }
 
         //Handle the fractional part
-        if self.peek() == '.' && self.peek_ahead(1).is_digit(10) {
+        let mut fractional = false;
+        if self.peek() == '.' && self.peek_ahead(1).is_ascii_digit() {
+            fractional = true;
             self.advance();
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+
+        //`1.2.3` is one bad literal, rather than 1.2 followed by a property access
+        if fractional && self.peek() == '.' && self.peek_ahead(1).is_ascii_digit() {
+            while self.peek() == '.' && self.peek_ahead(1).is_ascii_digit() || self.peek().is_ascii_digit() {
                 self.advance();
             }
+            let message = format!("Invalid number literal `{}`: a number has at most one decimal point.", self.span_string());
+            error(&self.index, self.start, ErrorCode::InvalidNumber, message);
+            return;
         }
 
         let lit = self.span_string();
-        self.add_token_lit(TTy::Number, TLit::Number(lit.parse().expect("Invalid digit")));
+        //Only ASCII digits and `.` get here, and str::parse ignores the locale,
+        //so a literal means the same everywhere.
+        //Integers that overflow an i64 are kept as (imprecise) floats instead.
+        let value = match (lit.parse(), lit.parse()) {
+            (Ok(int), _) if !fractional => TLit::Int(int),
+            (_, Ok(num)) => TLit::Number(num),
+            //Digits always parse as a float, but a bad literal is the script's problem, not a crash.
+            (_, Err(_)) => {
+                error(&self.index, self.start, ErrorCode::InvalidNumber, "Invalid number literal.");
+                return;
+            },
+        };
+        if let TLit::Number(num) = value {
+            self.warn_precision_loss(&lit, num);
+        }
+        self.add_token_lit(TTy::Number, value);
+    }
+
+    //Warns when a float literal doesn't keep its exact value: too many digits, or too large for an f64.
+    fn warn_precision_loss(&self, lit: &str, num: f64) {
+        //Integers are written out in full, since every f64 that large is a whole number.
+        //Fractions are written the shortest way that reads back as the same f64.
+        let value = match lit.contains('.') {
+            true => num.to_string(),
+            false => format!("{num:.0}"),
+        };
+        let message = if num.is_infinite() {
+            "Number literal is too large, and becomes infinity.".to_owned()
+        } else if value != normalize_number(lit) {
+            format!("Number literal `{lit}` can't be represented exactly, and becomes {value}.")
+        } else {
+            return;
+        };
+        warning(&self.index, self.start, Lint::PrecisionLoss, message);
     }
 
     //Reads in an identifier.
@@ -204,33 +709,14 @@ impl Scanner {
         }
 
         let span = self.span_string();
-        let ty = match span.as_str() {
-            "and" => And,
-            "class" => Class,
-            "else" => Else,
-            "for" => For,
-            "fn" => Fn,
-            "if" => If,
-            "null" => Null,
-            "or" => Or,
-            "print" => Print,
-            "ret" => Return,
-            "super" => Super,
-            "self" => This,
-            "var" => Var,
-            "while" => While,
-            //True and False are intentionally proper cased- I think it looks better.
-            "True" => {
-                self.add_token_lit(True, TLit::Bool(true));
-                return;
-            },
-            "False" => {
-                self.add_token_lit(False, TLit::Bool(false));
-                return;
-            },
-            _ => Ident,
-        };
-        self.add_token(ty);
+        let ty = self.keywords()
+            .find(|(keyword, _)| *keyword == span)
+            .map_or(Ident, |(_, ty)| ty.clone());
+        match ty {
+            True => self.add_token_lit(True, TLit::Bool(true)),
+            False => self.add_token_lit(False, TLit::Bool(false)),
+            _ => self.add_token(ty),
+        }
     }
 
     //Read the next char or return null if it's out of bounds.
@@ -251,6 +737,16 @@ impl Scanner {
         self.buf[self.current + offset]
     }
 
+    //Is the scanner positioned at the start of a line break?
+    fn at_line_break(&self) -> bool {
+        line_break_len(&self.buf, self.current).is_some()
+    }
+
+    //The line the scanner is currently on.
+    fn line(&self) -> usize {
+        self.index.line_of(self.current)
+    }
+
     //Read the next char and advance the position
     fn advance(&mut self) -> char {
         self.current += 1;
@@ -266,6 +762,252 @@ impl Scanner {
     //Add a token and associated literal to the list
     fn add_token_lit(&mut self, ty: TTy, lit: TLit) {
         let src: String = self.span_string();
-        self.tokens.push(Token::new(ty, src, lit, self.line));
+        let span = self.offset + self.start .. self.offset + self.current;
+        self.tokens.push(Token::new(ty, src, lit, self.line(), span));
+    }
+}
+
+//A number literal as f64's Display would write its value, if it were exact:
+//no leading zeros before the point, and no trailing zeros (or point) after it.
+fn normalize_number(lit: &str) -> String {
+    let (int, frac) = lit.split_once('.').unwrap_or((lit, ""));
+    let int = match int.trim_start_matches('0') {
+        "" => "0",
+        int => int,
+    };
+    match frac.trim_end_matches('0') {
+        "" => int.to_owned(),
+        frac => format!("{int}.{frac}"),
+    }
+}
+
+//How indentation made of `ch` is described in warnings.
+fn indent_name(ch: char) -> &'static str {
+    match ch {
+        '\t' => "tabs",
+        _ => "spaces",
+    }
+}
+
+//The kinds of string literal, by what closes them
+#[derive(Clone, Copy, PartialEq)]
+enum StringKind {
+    //"...", which also ends at the end of its line
+    Plain,
+    //r#"..."#, closed by a quote and this many #s
+    Raw(usize),
+    //"""..."""
+    Triple,
+}
+
+impl StringKind {
+    //If the string's closing delimiter is at buf[i], its length.
+    fn closing_len(self, buf: &[char], i: usize) -> Option<usize> {
+        let closing: Vec<char> = match self {
+            StringKind::Plain => vec!['"'],
+            StringKind::Raw(hashes) => std::iter::once('"').chain(std::iter::repeat_n('#', hashes)).collect(),
+            StringKind::Triple => vec!['"'; 3],
+        };
+        buf[i ..].starts_with(&closing).then_some(closing.len())
+    }
+}
+
+//The text of a triple-quoted string. A line break straight after the opening quotes is dropped, and so
+//is the last line if it only indents the closing quotes. Then the indentation every line shares (blank
+//lines aside) is removed, so the string can be indented along with the code around it:
+//    var s = """
+//        Hello,
+//          world
+//        """;
+//is "Hello,\n  world\n". A string on a single line is kept as written.
+fn strip_indent(text: &[char]) -> String {
+    //Each line, and the line break that ends it
+    let mut lines: Vec<(&[char], &[char])> = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        match line_break_len(text, i) {
+            Some(len) => {
+                lines.push((&text[start .. i], &text[i .. i + len]));
+                i += len;
+                start = i;
+            },
+            None => i += 1,
+        }
+    }
+    lines.push((&text[start ..], &[]));
+    if lines.len() == 1 {
+        return text.iter().collect();
+    }
+
+    let is_blank = |line: &[char]| line.iter().all(|&ch| ch == ' ' || ch == '\t');
+    let indent_of = |line: &[char]| line.iter().take_while(|&&ch| ch == ' ' || ch == '\t').count();
+    if is_blank(lines[0].0) {
+        lines.remove(0);
+    }
+    //The closing quotes' line: it has no text, but its indentation counts
+    let mut indent = usize::MAX;
+    if let Some(&(last, _)) = lines.last().filter(|(last, _)| is_blank(last)) {
+        indent = last.len();
+        lines.pop();
+    }
+    indent = lines.iter()
+        .filter(|(line, _)| !is_blank(line))
+        .map(|(line, _)| indent_of(line))
+        .fold(indent, usize::min);
+
+    lines.iter()
+        .flat_map(|&(line, line_break)| {
+            let text = if is_blank(line) { &[] } else { &line[indent.min(line.len()) ..] };
+            text.iter().chain(line_break)
+        })
+        .collect()
+}
+
+//If the chars at `i` open a raw string (after its `r`), the number of #s before the quote.
+fn raw_string_hashes(buf: &[char], i: usize) -> Option<usize> {
+    let hashes = buf[i.min(buf.len()) ..].iter().take_while(|&&ch| ch == '#').count();
+    (buf.get(i + hashes) == Some(&'"')).then_some(hashes)
+}
+
+//Where a char literal starting at `i` (after its opening quote) stops: at the closing quote,
+//or at the line break or end of file that leaves it unterminated.
+//A backslash escapes the char after it, so '\'' holds a single quote.
+fn char_literal_end(buf: &[char], mut i: usize) -> usize {
+    let at_end = |i: usize| i >= buf.len() || line_break_len(buf, i).is_some();
+    while !at_end(i) && buf[i] != '\'' {
+        if buf[i] == '\\' && !at_end(i + 1) {
+            i += 1;
+        }
+        i += 1;
+    }
+    i
+}
+
+//The char an escape sequence in a char literal (after its backslash) stands for.
+//Besides the usual single char escapes, any char can be written as `\u{hex}`.
+fn char_escape(escape: &[char]) -> Option<char> {
+    match escape {
+        ['n'] => Some('\n'),
+        ['r'] => Some('\r'),
+        ['t'] => Some('\t'),
+        ['0'] => Some('\0'),
+        [ch @ ('\\' | '\'' | '"')] => Some(*ch),
+        ['u', '{', hex @ .., '}'] => char::from_u32(u32::from_str_radix(&hex.iter().collect::<String>(), 16).ok()?),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::error_log::capture;
+
+    //Chars that make up awkward scripts: every kind of string, comments, chars, numbers and line breaks.
+    const ALPHABET: &[char] = &['"', '"', '"', '\n', '\n', '\r', '\u{2028}', ' ', ' ', '\t', 'r', 'r', '#', 'a', 'b', '1', '.', '/', '/', '\\', '\'', '(', ')', '{', '}', '=', '<', ';', 'é'];
+
+    /// A reproducible random script of `len` chars.
+    pub(crate) fn random_source(rng: &mut u64, len: usize) -> String {
+        (0 .. len).map(|_| ALPHABET[random(rng) % ALPHABET.len()]).collect()
+    }
+
+    /// xorshift64: deterministic, so a failure always reproduces.
+    pub(crate) fn random(rng: &mut u64) -> usize {
+        *rng ^= *rng << 13;
+        *rng ^= *rng >> 7;
+        *rng ^= *rng << 17;
+        *rng as usize
+    }
+
+    /// Diagnostics as comparable text, ignoring the order threads reported them in.
+    pub(crate) fn sorted(diagnostics: Vec<crate::error_log::Diagnostic>) -> Vec<String> {
+        let mut diagnostics: Vec<String> = diagnostics.iter().map(|diagnostic| format!("{diagnostic:?}")).collect();
+        diagnostics.sort();
+        diagnostics
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parallel_scan_matches_sequential_scan() {
+        let mut rng = 0x1234_5678_9abc_def1;
+        let mut split = 0;
+        for len in [60, 200] {
+            for _ in 0 .. 2000 {
+                let source = random_source(&mut rng, len);
+                let scanner = || Scanner::with_options(source.clone(), ScanOptions { check_round_trip: true, ..ScanOptions::default() });
+                if scanner().chunk_bounds(8, 8).len() > 2 {
+                    split += 1;
+                }
+
+                let (sequential, expected) = capture(|| scanner().scan_tokens());
+                assert!(expected.iter().all(|diagnostic| diagnostic.code.as_deref() != Some("E008")), "{source:?} doesn't round-trip");
+                let (parallel, diagnostics) = capture(|| scanner().scan_chunked(8, 8));
+                assert_eq!(parallel, sequential, "{source:?}");
+                assert_eq!(sorted(diagnostics), sorted(expected), "{source:?}");
+            }
+        }
+        //Most scripts should really have been split
+        assert!(split > 2000, "only {split} scripts were split");
+    }
+
+    //The value of the string literal that makes up `source`
+    fn string(source: &str) -> String {
+        let (tokens, diagnostics) = capture(|| Scanner::with_options(source.into(), ScanOptions::default()).scan_tokens());
+        assert!(diagnostics.is_empty(), "{source:?}: {diagnostics:?}");
+        match tokens[0].literal() {
+            TLit::String(s) => s.clone(),
+            literal => panic!("{source:?} is {literal:?}"),
+        }
+    }
+
+    #[test]
+    fn triple_quoted_strings_drop_the_leading_blank_line() {
+        assert_eq!(string("\"\"\"\nHello\"\"\""), "Hello");
+        assert_eq!(string("\"\"\"  \t\r\nHello\"\"\""), "Hello");
+        //Only the first line, and only if it's blank
+        assert_eq!(string("\"\"\"\n\nHello\"\"\""), "\nHello");
+        assert_eq!(string("\"\"\"Hi\n  there\"\"\""), "Hi\n  there");
+    }
+
+    #[test]
+    fn triple_quoted_strings_strip_the_closing_quotes_indentation() {
+        assert_eq!(string("\"\"\"\n    Hello,\n      world\n    \"\"\""), "Hello,\n  world\n");
+        //The closing quotes can keep some of the indentation in the string
+        assert_eq!(string("\"\"\"\n    a\n  \"\"\""), "  a\n");
+        //But the least indented line still sets the limit
+        assert_eq!(string("\"\"\"\n  a\n    b\n      \"\"\""), "a\n  b\n");
+        //Closing quotes after text don't count
+        assert_eq!(string("\"\"\"\n    a\n    b\"\"\""), "a\nb");
+        //Line breaks are kept as written
+        assert_eq!(string("\"\"\"\r\n  a\r\n  b\r\n  \"\"\""), "a\r\nb\r\n");
+        assert_eq!(string("\"\"\"\n\"\"\""), "");
+    }
+
+    #[test]
+    fn triple_quoted_strings_keep_blank_lines() {
+        assert_eq!(string("\"\"\"\n    a\n\n    b\n    \"\"\""), "a\n\nb\n");
+        //Whitespace-only lines don't count towards the indentation, and are left empty
+        assert_eq!(string("\"\"\"\n    a\n  \n\t\n    b\n    \"\"\""), "a\n\n\nb\n");
+    }
+
+    #[test]
+    fn single_line_triple_quoted_strings_are_kept_as_written() {
+        assert_eq!(string("\"\"\"x\"\"\""), "x");
+        assert_eq!(string("\"\"\"  x  \"\"\""), "  x  ");
+        assert_eq!(string("\"\"\"say \"hi\" \"\"\""), "say \"hi\" ");
+        assert_eq!(string("\"\"\"\"\"\""), "");
+    }
+
+    #[test]
+    fn unterminated_triple_quoted_string_is_reported_at_its_start() {
+        let source = "var a = 1;\nvar s = \"\"\"\n  text\n  \"\";\nprint s;\n";
+        let (tokens, diagnostics) = capture(|| Scanner::with_options(source.into(), ScanOptions::default()).scan_tokens());
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!((diagnostic.code.as_deref(), diagnostic.line, diagnostic.column), (Some("E002"), 2, 9));
+        assert_eq!(diagnostic.message, "Unterminated multi-line string literal (expected `\"\"\"` to close it).");
+        //Everything after the opening quotes was swallowed
+        let types: Vec<&TTy> = tokens.iter().map(Token::ty).collect();
+        assert_eq!(types, [&TTy::Var, &TTy::Ident, &TTy::Eq, &TTy::Number, &TTy::Semicolon, &TTy::Var, &TTy::Ident, &TTy::Eq, &TTy::EOF]);
+    }
+}
@@ -0,0 +1,33 @@
+/// Settings that change how source code is scanned.
+/// Anything here can change the scanner's output, so it's part of the token cache key.
+#[derive(Debug, Clone)]
+pub(crate) struct ScanOptions {
+    //The longest allowed string literal, in chars.
+    pub max_string_len: usize,
+    //The deepest allowed nesting of (), [] and {}. Keeps later recursive phases
+    //from overflowing the stack on adversarial input like 100k open parens.
+    pub max_nesting: usize,
+    //End statements at line breaks where a `;` could go, so it can be left out.
+    pub infer_semicolons: bool,
+    //After scanning, check that the tokens and trivia reproduce the source exactly,
+    //reporting any mismatch as a scanner bug. For testing changes to the scanner.
+    pub check_round_trip: bool,
+    //The dialect where `print` is a native function, `print("hi", x)`, rather than a statement.
+    //It's scanned as an identifier, so it can be passed around like any other function.
+    pub print_fn: bool,
+    //How many columns apart tab stops are, for the columns in diagnostics.
+    pub tab_width: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_string_len: 1 << 20,
+            max_nesting: 256,
+            infer_semicolons: false,
+            check_round_trip: false,
+            print_fn: false,
+            tab_width: 4,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use crate::tokens::{locate_bytes, TTy, TokenList};
+
+use crate::line_index::line_break_len;
+
+use super::{raw_string_hashes, semicolons::is_inferred, Scanner};
+
+//How many chars past its end a token's lexing can look at.
+//expect_number peeks at ".5" after "1", and expect_many at the ".." after ".".
+const LOOKAHEAD: usize = 2;
+
+/// A single edit to a source file: the chars in `range` (offsets into the
+/// old source) were replaced with `text`.
+//Until there's an editor integration, only the tests build edits.
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Scanner {
+    /// Lexes the edited source this scanner was created with, reusing the tokens
+    /// from before the edit (`previous`) wherever the edit can't have affected them.
+    /// Only the damaged region is actually re-scanned; the result is identical to scan_tokens.
+    /// An edit that doesn't fit the previous tokens and the new source falls back to a full scan.
+    /// Errors in the reused text were reported when it was first scanned, so they aren't reported again;
+    /// the checks over the whole token list, like bracket nesting, are.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn relex(mut self, edit: &TextEdit, previous: &TokenList) -> TokenList {
+        let inserted = edit.text.chars().count();
+        //Where the edited text ends in the new source
+        let edit_end = edit.range.start + inserted;
+
+        //The old source ends where its EOF token is
+        let old_len = previous.last().filter(|token| *token.ty() == TTy::EOF).map(|token| token.span().end);
+        let fits = old_len.is_some_and(|old_len| {
+            edit.range.start <= edit.range.end && edit.range.end <= old_len
+                && old_len - edit.range.len() + inserted == self.buf.len()
+        });
+        if !fits {
+            return self.scan_tokens();
+        }
+
+        //Inferred semicolons depend on the tokens around them, so they're inferred again afterwards.
+        let previous: TokenList = previous.iter().filter(|token| !is_inferred(token)).cloned().collect();
+
+        //Tokens that end well before the edit lexed the same no matter what follows.
+        let mut kept = previous.iter()
+            .take_while(|token| *token.ty() != TTy::EOF && token.span().end + LOOKAHEAD < edit.range.start)
+            .count();
+        //Nor anything after a raw string left unterminated: see opens_string.
+        let mut gap_start = 0;
+        for (i, token) in previous[.. kept].iter().enumerate() {
+            if opens_string(&self.buf, gap_start .. token.span().start) {
+                kept = i;
+                break;
+            }
+            gap_start = token.span().end;
+        }
+        //Except an `r`: any number of #s may come between it and the quote making it a raw string.
+        if kept > 0 && previous[kept - 1].lexeme() == "r" {
+            kept -= 1;
+        }
+        self.tokens = previous[.. kept].to_vec();
+        //The end of a token is always a safe place to resume scanning from.
+        self.current = self.tokens.last().map_or(0, |token| token.span().end);
+        if self.current == 0 {
+            self.skip_shebang();
+        }
+
+        let mut rest = previous[kept ..].iter()
+            .filter(|token| *token.ty() != TTy::EOF)
+            .peekable();
+        while !self.reached_eof() {
+            //Past the edit, the old and new sources are the same text. If the old scan started a token
+            //at the equivalent position, the scanner was in the same state there, so everything from
+            //here on lexes exactly as it did before: reuse it.
+            if self.current >= edit_end {
+                let old_pos = self.current - edit_end + edit.range.end;
+                while rest.next_if(|token| token.span().start < old_pos).is_some() {}
+
+                if rest.peek().is_some_and(|token| token.span().start == old_pos) {
+                    for token in rest {
+                        let start = token.span().start - edit.range.end + edit_end;
+                        let line = self.index.line_of(start + token.span().len());
+                        self.tokens.push(token.relocated(start, line));
+                    }
+                    self.current = self.buf.len();
+                    break;
+                }
+            }
+
+            self.scan_next();
+        }
+
+        self.push_eof();
+        if self.options.infer_semicolons {
+            self.infer_semicolons();
+        }
+        locate_bytes(&mut self.tokens, &self.buf);
+        self.check_tokens();
+        self.tokens
+    }
+}
+
+//Does the text between two tokens hold the start of a raw string that was never closed?
+//Only whitespace, comments and rejected text come between tokens, and of those, only an unterminated
+//raw string starts with `r#"`. It was cut off at the end of its line because nothing after it closed it,
+//so an edit anywhere later can change how it, and everything after it, lexes.
+//(Ordinary strings always end with their line, and an unterminated triple-quoted one runs to the end of the file.)
+fn opens_string(buf: &[char], gap: Range<usize>) -> bool {
+    let mut i = gap.start;
+    while i < gap.end {
+        match buf[i] {
+            'r' if raw_string_hashes(buf, i + 1).is_some() => return true,
+            //Quotes in comments don't count
+            '/' if buf.get(i + 1) == Some(&'/') => {
+                while i < gap.end && line_break_len(buf, i).is_none() {
+                    i += 1;
+                }
+            },
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error_log::capture, scanner::{tests::{random, random_source, sorted}, ScanOptions, Scanner, TextEdit}};
+
+    //Pieces of valid scripts, so the text before an edit scans without errors
+    const PIECES: &[&str] = &["var", "fn", "ret", "x", "r", "1", "1.5", " ", "\n", "\r\n", "\t", "\"s\"", "\"\"", "\"\"\"a\n  b\"\"\"",
+        "r#\"q\"\n\"#", "'c'", "// c\n", "/// d\n", "(", ")", "{", "}", "[", "]", "=", "==", "<", ";", ".", "..", "..=", "?."];
+
+    #[test]
+    fn relex_matches_a_fresh_scan() {
+        let mut rng = 0x9e37_79b9_7f4a_7c15;
+        for infer_semicolons in [false, true] {
+            let options = ScanOptions { infer_semicolons, ..ScanOptions::default() };
+            let mut tried = 0;
+            while tried < 3000 {
+                let old: String = (0 .. 40).map(|_| PIECES[random(&mut rng) % PIECES.len()]).collect();
+                let (previous, diagnostics) = capture(|| Scanner::with_options(old.clone(), options.clone()).scan_tokens());
+                //Errors in reused text aren't reported again, so only edits to clean scripts can be compared
+                if !diagnostics.is_empty() {
+                    continue;
+                }
+                tried += 1;
+
+                let chars: Vec<char> = old.chars().collect();
+                let start = random(&mut rng) % (chars.len() + 1);
+                let end = (start + random(&mut rng) % 4).min(chars.len());
+                let len = random(&mut rng) % 4;
+                let edit = TextEdit { range: start .. end, text: random_source(&mut rng, len) };
+                let new: String = chars[.. start].iter().chain(edit.text.chars().collect::<Vec<_>>().iter()).chain(&chars[end ..]).collect();
+
+                let (expected, expected_diagnostics) = capture(|| Scanner::with_options(new.clone(), options.clone()).scan_tokens());
+                let (tokens, diagnostics) = capture(|| Scanner::with_options(new.clone(), options.clone()).relex(&edit, &previous));
+                assert_eq!(tokens, expected, "{old:?} with {:?} at {:?}", edit.text, edit.range);
+                assert_eq!(sorted(diagnostics), sorted(expected_diagnostics), "{old:?} with {:?} at {:?}", edit.text, edit.range);
+            }
+        }
+    }
+
+    #[test]
+    fn edits_that_dont_fit_rescan_everything() {
+        let options = ScanOptions::default();
+        let previous = Scanner::with_options("var x = 1;".into(), options.clone()).scan_tokens();
+        //The range is past the end of the old source
+        let edit = TextEdit { range: 20 .. 25, text: "y".into() };
+        let source = "var y = 2;".to_owned();
+        let (tokens, _) = capture(|| Scanner::with_options(source.clone(), options.clone()).relex(&edit, &previous));
+        assert_eq!(tokens, Scanner::with_options(source, options).scan_tokens());
+    }
+}
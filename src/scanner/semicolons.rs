@@ -0,0 +1,118 @@
+use crate::tokens::{TLit, TTy, Token};
+
+use super::Scanner;
+
+impl Scanner {
+    //With options.infer_semicolons, a line break ends a statement whose expression looks complete,
+    //as in Go and Swift: a `;` is inserted after the last token on the line. Runs over the finished tokens.
+    //Inferred semicolons have an empty lexeme and span, right after the token they follow.
+    pub(super) fn infer_semicolons(&mut self) {
+        use TTy::*;
+
+        let tokens = std::mem::take(&mut self.tokens);
+        //Open () and [] around the current token, which statements can't end inside.
+        //Each is true if it's the `(` of an if, while or for header.
+        let mut open: Vec<bool> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            //`if (x)` is followed by its body, often on the next line
+            let mut closes_header = false;
+            match token.ty() {
+                LParen => open.push(i > 0 && matches!(tokens[i - 1].ty(), If | While | For)),
+                LBracket => open.push(false),
+                RParen | RBracket => closes_header = open.pop() == Some(true),
+                _ => {}
+            }
+            self.tokens.push(token.clone());
+
+            let Some(next) = tokens.get(i + 1) else {
+                continue;
+            };
+            //A closing brace ends the last statement of a block, even on the same line: `{ print x }`
+            let at_end = *next.ty() == RBrace || *next.ty() == EOF
+                || self.index.line_of(next.span().start - self.offset) > self.index.line_of(token.span().end - self.offset);
+            if open.is_empty() && !closes_header && at_end && ends_statement(token.ty()) && !continues_statement(next.ty()) {
+                let end = token.span().end;
+                self.tokens.push(Token::new(Semicolon, "", TLit::Null, token.line(), end .. end));
+            }
+        }
+    }
+}
+
+/// Is this a semicolon inserted by semicolon inference, rather than written in the source?
+pub(super) fn is_inferred(token: &Token) -> bool {
+    *token.ty() == TTy::Semicolon && token.span().is_empty()
+}
+
+//Can an expression (or a bare `ret` or `yield`) end with this token?
+fn ends_statement(ty: &TTy) -> bool {
+    use TTy::*;
+    matches!(ty, Ident | String | Number | Char | True | False | Null | This | RParen | RBracket | Return | Yield)
+}
+
+//Does a line starting with this token carry on the statement before it?
+//`-` is left out: `-x` on its own line is more likely than a subtraction split across lines.
+fn continues_statement(ty: &TTy) -> bool {
+    use TTy::*;
+    matches!(ty,
+        Semicolon | LBrace | Period | QuestionDot | QuestionQuestion | Pipe | Comma | Plus | Asterisk | FSlash
+        | Eq | EqEq | BangEq | Gt | GtEq | Lt | LtEq | And | Or | Is | DotDot | DotDotEq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_inferred;
+    use crate::{error_log::capture, scanner::{ScanOptions, Scanner}, tokens::TTy};
+
+    //The tokens of `source` with inferred semicolons, as their lexemes: `var x = 1 ;`
+    fn inferred(source: &str) -> String {
+        let options = ScanOptions { infer_semicolons: true, ..ScanOptions::default() };
+        let (tokens, diagnostics) = capture(|| Scanner::with_options(source.into(), options).scan_tokens());
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        tokens.iter().filter(|token| *token.ty() != TTy::EOF)
+            .map(|token| if is_inferred(token) { ";" } else { token.lexeme() })
+            .collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn line_breaks_end_statements() {
+        assert_eq!(inferred("var x = 1\nprint x\n"), "var x = 1 ; print x ;");
+        assert_eq!(inferred("var x = 1;\nprint x"), "var x = 1 ; print x ;");
+        //A closing brace ends the statement before it, even on the same line
+        assert_eq!(inferred("{ print x }"), "{ print x ; }");
+        //Not after something an expression can't end with
+        assert_eq!(inferred("var x =\n  1"), "var x = 1 ;");
+    }
+
+    #[test]
+    fn continuation_operators() {
+        assert_eq!(inferred("var x = a\n  + b\n  * c\n  .d\n  ?.e\n  ?? f\n  |> g\n  or h"), "var x = a + b * c . d ?. e ?? f |> g or h ;");
+        assert_eq!(inferred("var x = a\n  == b\n  and c\n  is D"), "var x = a == b and c is D ;");
+        assert_eq!(inferred("fn f() {}\n{}"), "fn f ( ) { } { }");
+        //`-x` on its own line is a statement of its own
+        assert_eq!(inferred("x\n-y"), "x ; - y ;");
+    }
+
+    #[test]
+    fn open_brackets() {
+        assert_eq!(inferred("f(a,\n  b\n)\ng()"), "f ( a , b ) ; g ( ) ;");
+        assert_eq!(inferred("var xs = [\n  1\n  2\n]"), "var xs = [ 1 2 ] ;");
+        //Braces aren't brackets: statements end inside blocks
+        assert_eq!(inferred("fn f() {\n  print x\n  print y\n}"), "fn f ( ) { print x ; print y ; }");
+    }
+
+    #[test]
+    fn bare_ret_and_yield() {
+        assert_eq!(inferred("fn f() {\n  ret\n}"), "fn f ( ) { ret ; }");
+        assert_eq!(inferred("fn f() {\n  yield\n  ret x\n}"), "fn f ( ) { yield ; ret x ; }");
+    }
+
+    #[test]
+    fn headers_are_followed_by_their_body() {
+        assert_eq!(inferred("if (x)\n  print x\nelse\n  print y"), "if ( x ) print x ; else print y ;");
+        assert_eq!(inferred("while (f(x))\n  x = g(x)"), "while ( f ( x ) ) x = g ( x ) ;");
+        assert_eq!(inferred("for (var i = 0; i < n; i = i + 1)\n  print i"), "for ( var i = 0 ; i < n ; i = i + 1 ) print i ;");
+        //Only the header's own paren: a call ending a body still ends the statement
+        assert_eq!(inferred("if (x) f(x)\ng()"), "if ( x ) f ( x ) ; g ( ) ;");
+        assert_eq!(inferred("if (x) (y)\nz"), "if ( x ) ( y ) ; z ;");
+    }
+}
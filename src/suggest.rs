@@ -0,0 +1,37 @@
+//Helpers for "did you mean ...?" suggestions.
+
+/// The edit distance between two strings: how many single char insertions,
+/// deletions, substitutions or swaps of adjacent chars turn one into the other.
+/// (Levenshtein distance, plus transpositions, since `esle` is a very common typo.)
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    //dist[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    dist[0] = (0 ..= b.len()).collect();
+
+    for i in 1 ..= a.len() {
+        for j in 1 ..= b.len() {
+            let substitute = dist[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            dist[i][j] = substitute.min(dist[i - 1][j] + 1).min(dist[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
+/// The candidate closest to `word`, if any is within `max_distance` edits.
+/// Exact matches aren't suggestions, so they're skipped.
+pub(crate) fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>, max_distance: usize) -> Option<&'a str> {
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
@@ -1,39 +1,106 @@
+use std::{fmt::{Display, Formatter}, ops::Range};
+
 /// All accepted token types in the language
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub(crate) enum TTy {
     //Single char
-    LParen, RParen, LBrace, RBrace, Comma, Period, Minus, Plus, Semicolon, FSlash, Asterisk,
+    LParen, RParen, LBrace, RBrace, LBracket, RBracket, Comma, Period, Minus, Plus, Semicolon, FSlash, Asterisk,
 
     //1+ char
     Bang, BangEq, Eq, EqEq, Gt, GtEq, Lt, LtEq,
+    //Ranges: `1..10` (exclusive) and `1..=10` (inclusive)
+    DotDot, DotDotEq,
+    //Rest parameters, `fn f(a, ...rest)`, and call-site spreading, `f(...list)`
+    Ellipsis,
+    //Null-safety: `a?.b` (safe property access) and `a ?? b` (null-coalescing)
+    QuestionDot, QuestionQuestion,
+    //Pipelines: `x |> f |> g(2)` means `g(f(x), 2)`
+    Pipe,
 
     //Many chars
     Ident, String, Number,
+    //Char literals: 'a', '\n'
+    Char,
 
     //Reserved keywords
     And, Class, Else, False, Fn, For, If, Null, Or,
     Print, Return, Super, This, True, Var, While,
+    //Type tests: `x is Number`
+    Is,
+    //Immutable bindings: `const x = 1;`
+    Const,
+    //Generators: `yield value;` suspends the function, producing the value
+    Yield,
+    //Deferred cleanup: `defer stmt;` runs stmt when the enclosing block exits
+    Defer,
+    //Enumerations: `enum Color { Red, Green, Blue }`
+    Enum,
 
     //The end of the script
     EOF,
 }
 
+#[allow(dead_code)]
+impl TTy {
+    /// Is this a reserved word? `True` and `False` are, even though they're also literals.
+    pub fn is_keyword(&self) -> bool {
+        use TTy::*;
+        matches!(self,
+            And | Class | Else | False | Fn | For | If | Null | Or | Print | Return | Super | This | True
+            | Var | While | Is | Const | Yield | Defer | Enum)
+    }
+
+    /// Is this a symbolic operator, like `+` or `?.`? Keyword operators (`and`, `or`, `is`)
+    /// are keywords, and brackets and separators are punctuation, not operators.
+    pub fn is_operator(&self) -> bool {
+        use TTy::*;
+        matches!(self,
+            Period | Minus | Plus | FSlash | Asterisk | Bang | BangEq | Eq | EqEq | Gt | GtEq | Lt | LtEq
+            | DotDot | DotDotEq | Ellipsis | QuestionDot | QuestionQuestion | Pipe)
+    }
+}
+
 /// Associated literals for some tokens
 // TODO: Place these in TTy variants
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
 pub(crate) enum TLit {
     //Literal `null`
     Null,
-    //Any number (always floating point)
+    //Integer literals without a fractional part: 42
+    Int(i64),
+    //Floating point literals: 3.14
+    //Also used for integer literals too large to fit in an i64.
     Number(f64),
     //String literals: "hello world"
     String(String),
+    //Char literals, with escapes already applied: 'a', '\n'
+    Char(char),
     //Boolean literals: true, false
     Bool(bool),
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
+/// How a value is written out, as `print` will show it. Doesn't depend on the locale.
+/// Numbers read back as the same value: floats use the fewest digits that do, so `0.1 + 0.2`
+/// is `0.30000000000000004`, and always have a point, so they aren't read back as integers.
+impl Display for TLit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TLit::Null => write!(f, "null"),
+            TLit::Int(int) => write!(f, "{int}"),
+            //Display never uses an exponent, so whole floats are all digits: 1e20 is 100000000000000000000.0
+            TLit::Number(num) if num.is_finite() && num.fract() == 0.0 => write!(f, "{num}.0"),
+            TLit::Number(num) => write!(f, "{num}"),
+            TLit::String(s) => write!(f, "{s}"),
+            TLit::Char(ch) => write!(f, "{ch}"),
+            TLit::Bool(true) => write!(f, "True"),
+            TLit::Bool(false) => write!(f, "False"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct Token {
     //The type of this token
     ty: TTy,
@@ -44,11 +111,89 @@ pub(crate) struct Token {
     literal: TLit,
     //Error reporting: what line in the code this token was parsed from.
     line: usize,
+    //The char offsets of the lexeme in the source code.
+    span: Range<usize>,
+    //The byte offsets of the lexeme in the source code, for slicing the source string.
+    //Filled in from the span by locate_bytes once the whole token list is known.
+    bytes: Range<usize>,
+}
+
+/// The tokens of a whole script, ending with EOF.
+pub(crate) type TokenList = Vec<Token>;
+
+/// Kinds of source text that aren't part of any token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TriviaKind {
+    //`// ...`
+    Comment,
+    //`/// ...`, documenting the declaration that follows it
+    DocComment,
+    //Spaces, tabs and line breaks
+    Whitespace,
+    //Text the scanner rejected, like an unexpected char or an unterminated string
+    Skipped,
+}
+
+/// A piece of source text the scanner skipped over, such as a comment.
+/// Together, the tokens and trivia of a script cover every char of it.
+#[derive(Debug, Clone)]
+pub(crate) struct Trivia {
+    pub kind: TriviaKind,
+    //The char offsets of the trivia in the source code.
+    pub span: Range<usize>,
 }
 
 #[allow(dead_code)]
 impl Token {
-    pub fn new(ty: TTy, lexeme: impl ToString, literal: TLit, line: usize) -> Self {
-        Self { ty, lexeme: lexeme.to_string(), literal, line }
+    pub fn new(ty: TTy, lexeme: impl ToString, literal: TLit, line: usize, span: Range<usize>) -> Self {
+        Self { ty, lexeme: lexeme.to_string(), literal, line, span, bytes: 0 .. 0 }
+    }
+
+    pub fn ty(&self) -> &TTy {
+        &self.ty
+    }
+
+    pub fn lexeme(&self) -> &str {
+        &self.lexeme
+    }
+
+    pub fn literal(&self) -> &TLit {
+        &self.literal
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
     }
-}
\ No newline at end of file
+
+    /// The byte range of the lexeme in the source string: `&source[token.source_range()] == token.lexeme()`.
+    pub fn source_range(&self) -> Range<usize> {
+        self.bytes.clone()
+    }
+
+    //A copy of this token moved to start at the char offset `start`, now on `line`.
+    //Used to reuse tokens that an edit only moved.
+    pub fn relocated(&self, start: usize, line: usize) -> Self {
+        Self { line, span: start .. start + self.span.len(), ..self.clone() }
+    }
+}
+
+/// Fills in the byte ranges of `tokens`, which must be in source order, from their char spans.
+/// Every list of tokens handed out (by the scanner or the cache) goes through this.
+pub(crate) fn locate_bytes(tokens: &mut [Token], source: &[char]) {
+    //The char offset reached so far, and its byte offset
+    let (mut pos, mut byte) = (0, 0);
+    let mut byte_at = |offset: usize| {
+        byte += source[pos .. offset].iter().map(|ch| ch.len_utf8()).sum::<usize>();
+        pos = offset;
+        byte
+    };
+
+    for token in tokens {
+        let start = byte_at(token.span.start);
+        token.bytes = start .. byte_at(token.span.end);
+    }
+}